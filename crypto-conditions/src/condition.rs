@@ -1,7 +1,25 @@
 use std::collections::HashSet;
 
-use crate::util::Base64Url;
+use crate::{der, util::Base64Url};
 
+// names of the condition types, by type id
+const TYPE_NAMES: [&str; 5] = [
+    "preimage-sha-256",
+    "prefix-sha-256",
+    "threshold-sha-256",
+    "rsa-sha-256",
+    "ed25519-sha-256",
+];
+
+pub fn type_name(type_id: usize) -> Option<&'static str> {
+    TYPE_NAMES.get(type_id).copied()
+}
+
+pub fn type_id(type_name: &str) -> Option<usize> {
+    TYPE_NAMES.iter().position(|name| *name == type_name)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Condition {
     pub hash: [u8; 32],
     pub type_id: usize,
@@ -26,6 +44,15 @@ impl Condition {
         self.subtypes = value;
     }
 
+    pub fn get_type_name(&self) -> &'static str {
+        type_name(self.type_id).unwrap_or_default()
+    }
+
+    // prefix and threshold conditions are made of other conditions
+    fn is_compound(&self) -> bool {
+        matches!(self.type_id, 1 | 2)
+    }
+
     pub fn serialize_uri(&self) -> String {
         // const ConditionClass = TypeRegistry.findByTypeId(this.type).Class;
         // const includeSubtypes = ConditionClass.TYPE_CATEGORY === 'compound';
@@ -36,13 +63,83 @@ impl Condition {
         //   (includeSubtypes ? '&subtypes=' + Array.from(this.getSubtypes()).sort().join(',') : '')
 
         let hash = Base64Url::encode(&self.hash);
-        let type_name = "ed25519-sha-256";
+        let type_name = self.get_type_name();
         let cost = self.cost;
 
-        let uri = format!("ni:///sha-256;{hash}?fpt={type_name}&cost={cost}");
+        let mut uri = format!("ni:///sha-256;{hash}?fpt={type_name}&cost={cost}");
+        if self.is_compound() {
+            let mut subtypes: Vec<&str> = self.subtypes.iter().map(String::as_str).collect();
+            subtypes.sort_unstable();
+            uri.push_str(&format!("&subtypes={}", subtypes.join(",")));
+        }
 
         uri
     }
+
+    pub fn serialize_binary(&self) -> Vec<u8> {
+        let mut contents = der::write(0x80, &self.hash);
+        contents.extend(der::write_integer(0x81, self.cost));
+        if self.is_compound() {
+            contents.extend(der::write(0x82, &subtypes_bit_string(&self.subtypes)));
+        }
+
+        der::write(0xa0 | self.type_id as u8, &contents)
+    }
+
+    pub fn parse_binary(bytes: &[u8]) -> Option<Self> {
+        let (tag, contents, rest) = der::read(bytes)?;
+        let type_id = (tag & 0x1f) as usize;
+        if !rest.is_empty() || tag & 0xe0 != 0xa0 || type_name(type_id).is_none() {
+            return None;
+        }
+
+        let (hash, cost, subtypes) = match der::read_elements(contents)?.as_slice() {
+            [(0x80, hash), (0x81, cost)] => (*hash, *cost, None),
+            [(0x80, hash), (0x81, cost), (0x82, subtypes)] => (*hash, *cost, Some(*subtypes)),
+            _ => return None,
+        };
+
+        Some(Self {
+            hash: hash.try_into().ok()?,
+            type_id,
+            cost: der::read_integer(cost)?,
+            subtypes: match subtypes {
+                Some(bits) => parse_subtypes_bit_string(bits)?,
+                None => HashSet::new(),
+            },
+        })
+    }
+}
+
+// `BIT STRING` with the bit of the type id of every subtype set
+fn subtypes_bit_string(subtypes: &HashSet<String>) -> Vec<u8> {
+    let ids: Vec<usize> = subtypes.iter().filter_map(|name| type_id(name)).collect();
+    let Some(last) = ids.iter().max() else {
+        return vec![0];
+    };
+
+    let mut bits = vec![0u8; last / 8 + 1];
+    for id in ids.iter() {
+        bits[id / 8] |= 0x80 >> (id % 8);
+    }
+    // the count of unused bits of the last byte comes first
+    bits.insert(0, (7 - last % 8) as u8);
+
+    bits
+}
+
+fn parse_subtypes_bit_string(bits: &[u8]) -> Option<HashSet<String>> {
+    let (_, bits) = bits.split_first()?;
+    let mut subtypes = HashSet::new();
+    for (index, byte) in bits.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                subtypes.insert(type_name(index * 8 + bit)?.to_string());
+            }
+        }
+    }
+
+    Some(subtypes)
 }
 
 #[cfg(test)]
@@ -64,4 +161,22 @@ mod tests {
 
         assert_eq!("ni:///sha-256;AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE?fpt=ed25519-sha-256&cost=100", uri);
     }
+
+    #[test]
+    fn test_serialize_binary() {
+        let condition = Condition {
+            hash: [1; 32],
+            type_id: 2,
+            cost: 264192,
+            subtypes: HashSet::from([String::from("ed25519-sha-256")]),
+        };
+
+        let binary = condition.serialize_binary();
+        assert_eq!(binary[..4], [0xa2, 0x2b, 0x80, 0x20]);
+        assert_eq!(binary[36..], [0x81, 3, 4, 8, 0, 0x82, 2, 3, 8]);
+        assert_eq!(Condition::parse_binary(&binary), Some(condition.clone()));
+        assert!(condition
+            .serialize_uri()
+            .ends_with("?fpt=threshold-sha-256&cost=264192&subtypes=ed25519-sha-256"));
+    }
 }
@@ -0,0 +1,132 @@
+//! Just enough DER to write and read the compound types, whose `SET OF` fields the `asn1`
+//! derives do not sort.
+
+// minimal big endian bytes of `value`
+fn be_bytes(value: usize) -> Vec<u8> {
+    value
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|byte| *byte == 0)
+        .collect()
+}
+
+pub fn write(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut buffer = vec![tag];
+    if contents.len() < 0x80 {
+        buffer.push(contents.len() as u8);
+    } else {
+        let length = be_bytes(contents.len());
+        buffer.push(0x80 | length.len() as u8);
+        buffer.extend(length);
+    }
+    buffer.extend_from_slice(contents);
+
+    buffer
+}
+
+pub fn write_integer(tag: u8, value: usize) -> Vec<u8> {
+    let mut bytes = be_bytes(value);
+    // a leading one bit would make the integer negative
+    if bytes.first().is_none() || bytes[0] & 0x80 != 0 {
+        bytes.insert(0, 0);
+    }
+
+    write(tag, &bytes)
+}
+
+/// `SET OF` the encoded `items`, sorted as DER requires.
+pub fn write_set(tag: u8, mut items: Vec<Vec<u8>>) -> Vec<u8> {
+    items.sort();
+    write(tag, &items.concat())
+}
+
+/// Split `input` into the tag and contents of its first element and the bytes after it.
+pub fn read(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (length, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > std::mem::size_of::<usize>() || rest.len() < count {
+            return None;
+        }
+        let (length, rest) = rest.split_at(count);
+        let length = length
+            .iter()
+            .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+        (length, rest)
+    };
+
+    if rest.len() < length {
+        return None;
+    }
+    let (contents, rest) = rest.split_at(length);
+    Some((tag, contents, rest))
+}
+
+/// Tags and contents of the elements of a `SEQUENCE` or `SET OF`.
+pub fn read_elements(mut contents: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut elements = Vec::new();
+    while !contents.is_empty() {
+        let (tag, element, rest) = read(contents)?;
+        elements.push((tag, element));
+        contents = rest;
+    }
+
+    Some(elements)
+}
+
+/// Whole encodings, tag and length included, of the elements of a `SEQUENCE` or `SET OF`.
+pub fn split_elements(mut contents: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut elements = Vec::new();
+    while !contents.is_empty() {
+        let (_, _, rest) = read(contents)?;
+        elements.push(&contents[..contents.len() - rest.len()]);
+        contents = rest;
+    }
+
+    Some(elements)
+}
+
+pub fn read_integer(contents: &[u8]) -> Option<usize> {
+    let contents = match contents {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        _ => contents,
+    };
+    if contents.is_empty() || contents.len() > std::mem::size_of::<usize>() {
+        return None;
+    }
+
+    Some(
+        contents
+            .iter()
+            .fold(0usize, |value, byte| (value << 8) | *byte as usize),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_read() {
+        assert_eq!(write_integer(0x81, 131072), [0x81, 3, 2, 0, 0]);
+        assert_eq!(write_integer(0x80, 128), [0x80, 2, 0, 128]);
+        assert_eq!(read_integer(&[0, 128]), Some(128));
+
+        let long = write(0xa0, &[7u8; 200]);
+        assert_eq!(long[..3], [0xa0, 0x81, 200]);
+        let (tag, contents, rest) = read(&long).unwrap();
+        assert_eq!((tag, contents.len(), rest.len()), (0xa0, 200, 0));
+        assert!(read(&long[..100]).is_none());
+
+        let set = write_set(0x31, vec![vec![2, 1, 9], vec![2, 1, 3]]);
+        assert_eq!(set, [0x31, 6, 2, 1, 3, 2, 1, 9]);
+        assert_eq!(
+            read_elements(&set[2..]).unwrap(),
+            vec![(2, &[3u8][..]), (2, &[9u8][..])]
+        );
+    }
+}
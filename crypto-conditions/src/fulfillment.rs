@@ -22,10 +22,15 @@ pub trait Fulfillment: Fingerprint {
             hash: self.generate_hash(),
             type_id: self.get_type_id(),
             cost: self.caculate_cost(),
-            subtypes: HashSet::new(),
+            subtypes: self.get_subtypes(),
         }
     }
 
+    // types of the subconditions of a compound condition
+    fn get_subtypes(&self) -> HashSet<String> {
+        HashSet::new()
+    }
+
     fn get_condition_uri(&self) -> String {
         self.get_condition().serialize_uri()
     }
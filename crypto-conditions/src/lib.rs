@@ -1,4 +1,5 @@
 pub mod condition;
+pub mod der;
 pub mod fulfillment;
 pub mod schema;
 pub mod types;
@@ -2,6 +2,7 @@ use bs58;
 use ring_compat::signature::{ed25519::SigningKey, Signer};
 
 use crate::{
+    der,
     fulfillment::Fulfillment,
     schema::{
         fingerprint::{Ed25519FingerprintContents, Fingerprint},
@@ -30,7 +31,7 @@ where
 
 pub type RingEd25519Signer = Ed25519Signer<SigningKey>;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Ed25519Sha256 {
     pub public_key: Option<[u8; 32]>,
     pub signature: Option<[u8; 64]>,
@@ -54,6 +55,22 @@ impl Ed25519Sha256 {
         self.public_key = Some(public_key);
     }
 
+    /// Read a fulfillment written by `serialize_binary`.
+    pub fn parse_binary(bytes: &[u8]) -> Option<Self> {
+        let (tag, contents, rest) = der::read(bytes)?;
+        if tag != 0xa4 || !rest.is_empty() {
+            return None;
+        }
+
+        match der::read_elements(contents)?.as_slice() {
+            [(0x80, public_key), (0x81, signature)] => Some(Self {
+                public_key: Some((*public_key).try_into().ok()?),
+                signature: Some((*signature).try_into().ok()?),
+            }),
+            _ => None,
+        }
+    }
+
     pub fn sign(&mut self, message: &[u8], private_key: &[u8; 32]) {
         let signing_key = SigningKey::from_bytes(private_key);
         let verifying_key = signing_key.verifying_key();
@@ -183,6 +200,9 @@ mod tests {
         hash.sign(message.as_bytes(), &buffer);
         let uri = hash.serialize_uri();
 
-        assert_eq!(uri, "pGSAIFkKUGDcxBrRc-k6GZd_C4LVMmJJge7S0sJ22p9w-Ma2gUDSzu3gXlYsjtEfA6IGriW92d53FRrvAgh9EvbUxIlmWKaf6N4zXPuBG8gRsX3HEAy_APschx40Iien8p_N760P")
+        assert_eq!(uri, "pGSAIFkKUGDcxBrRc-k6GZd_C4LVMmJJge7S0sJ22p9w-Ma2gUDSzu3gXlYsjtEfA6IGriW92d53FRrvAgh9EvbUxIlmWKaf6N4zXPuBG8gRsX3HEAy_APschx40Iien8p_N760P");
+
+        let parsed = Ed25519Sha256::parse_binary(&hash.serialize_binary()).unwrap();
+        assert_eq!(parsed, hash);
     }
 }
@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+
+use crate::{
+    condition::Condition, der, fulfillment::Fulfillment, schema::fingerprint::Fingerprint,
+    Ed25519Sha256,
+};
+
+/// Subcondition of a threshold, along with its fulfillment once known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Subcondition {
+    Condition(Condition),
+    Fulfillment(Ed25519Sha256),
+}
+
+impl Subcondition {
+    pub fn condition(&self) -> Condition {
+        match self {
+            Self::Condition(condition) => condition.clone(),
+            Self::Fulfillment(fulfillment) => fulfillment.get_condition(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThresholdSha256 {
+    pub threshold: usize,
+    pub subconditions: Vec<Subcondition>,
+}
+
+impl ThresholdSha256 {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            subconditions: Vec::new(),
+        }
+    }
+
+    pub fn add_subcondition(&mut self, condition: Condition) {
+        self.subconditions.push(Subcondition::Condition(condition));
+    }
+
+    pub fn add_subfulfillment(&mut self, fulfillment: Ed25519Sha256) {
+        self.subconditions
+            .push(Subcondition::Fulfillment(fulfillment));
+    }
+
+    pub fn subfulfillments(&self) -> impl Iterator<Item = &Ed25519Sha256> {
+        self.subconditions
+            .iter()
+            .filter_map(|subcondition| match subcondition {
+                Subcondition::Fulfillment(fulfillment) => Some(fulfillment),
+                Subcondition::Condition(_) => None,
+            })
+    }
+
+    /// Read a fulfillment written by `serialize_binary`, whose subfulfillments are ed25519
+    /// ones. The threshold is not written, so it is given by the caller, from the condition the
+    /// fulfillment is for. A fulfillment with more subfulfillments than `threshold`, or fewer
+    /// subconditions, is rejected.
+    pub fn parse_binary(bytes: &[u8], threshold: usize) -> Option<Self> {
+        let (tag, contents, rest) = der::read(bytes)?;
+        if tag != 0xa2 || !rest.is_empty() {
+            return None;
+        }
+        let [(0xa0, fulfillments), (0xa1, conditions)] = der::read_elements(contents)?[..] else {
+            return None;
+        };
+
+        let mut parsed = Self::new(threshold);
+        for fulfillment in der::split_elements(fulfillments)? {
+            parsed.add_subfulfillment(Ed25519Sha256::parse_binary(fulfillment)?);
+        }
+        if parsed.subconditions.len() > threshold {
+            return None;
+        }
+        for condition in der::split_elements(conditions)? {
+            parsed.add_subcondition(Condition::parse_binary(condition)?);
+        }
+        if parsed.subconditions.len() < threshold {
+            return None;
+        }
+
+        Some(parsed)
+    }
+}
+
+impl Fingerprint for ThresholdSha256 {
+    fn get_fingerprint_contents(&self) -> Vec<u8> {
+        let subconditions = self
+            .subconditions
+            .iter()
+            .map(|subcondition| subcondition.condition().serialize_binary())
+            .collect();
+
+        let mut contents = der::write_integer(0x80, self.threshold);
+        contents.extend(der::write_set(0xa1, subconditions));
+        der::write(0x30, &contents)
+    }
+}
+
+impl Fulfillment for ThresholdSha256 {
+    const TYPE_ID: usize = 2;
+    const TYPE_NAME: &'static str = "threshold-sha-256";
+    // added for each subcondition
+    const CONSTANT_COST: usize = 1024;
+
+    // the costs of the `threshold` most expensive subconditions, and a constant per subcondition
+    fn caculate_cost(&self) -> usize {
+        let mut costs: Vec<usize> = self
+            .subconditions
+            .iter()
+            .map(|subcondition| subcondition.condition().cost)
+            .collect();
+        costs.sort_unstable_by(|a, b| b.cmp(a));
+
+        costs.iter().take(self.threshold).sum::<usize>()
+            + Self::CONSTANT_COST * self.subconditions.len()
+    }
+
+    fn get_subtypes(&self) -> HashSet<String> {
+        let mut subtypes = HashSet::new();
+        for subcondition in self.subconditions.iter() {
+            let condition = subcondition.condition();
+            subtypes.insert(condition.get_type_name().to_string());
+            subtypes.extend(condition.subtypes);
+        }
+        subtypes.remove(Self::TYPE_NAME);
+
+        subtypes
+    }
+
+    // the first `threshold` subconditions fulfilled are written as fulfillments, the others as
+    // conditions
+    fn serialize_binary(&self) -> Vec<u8> {
+        let mut fulfillments = Vec::new();
+        let mut conditions = Vec::new();
+        for subcondition in self.subconditions.iter() {
+            match subcondition {
+                Subcondition::Fulfillment(fulfillment) if fulfillments.len() < self.threshold => {
+                    fulfillments.push(fulfillment.serialize_binary())
+                }
+                _ => conditions.push(subcondition.condition().serialize_binary()),
+            }
+        }
+
+        let mut contents = der::write_set(0xa0, fulfillments);
+        contents.extend(der::write_set(0xa1, conditions));
+        der::write(0xa2, &contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed(private_key: [u8; 32]) -> Ed25519Sha256 {
+        let mut fulfillment = Ed25519Sha256::new();
+        fulfillment.sign(b"Hello, world", &private_key);
+        fulfillment
+    }
+
+    #[test]
+    fn test_threshold_condition() {
+        let mut threshold = ThresholdSha256::new(2);
+        threshold.add_subfulfillment(signed([1; 32]));
+        threshold.add_subcondition(signed([2; 32]).get_condition());
+
+        assert_eq!(threshold.caculate_cost(), 2 * 131072 + 2 * 1024);
+        assert!(threshold
+            .get_condition_uri()
+            .ends_with("?fpt=threshold-sha-256&cost=264192&subtypes=ed25519-sha-256"));
+        assert_eq!(
+            threshold.get_fingerprint_contents()[..5],
+            [0x30, 0x57, 0x80, 1, 2]
+        );
+
+        // the subconditions are sorted, so their order does not change the condition
+        let mut reversed = ThresholdSha256::new(2);
+        reversed.add_subcondition(signed([2; 32]).get_condition());
+        reversed.add_subfulfillment(signed([1; 32]));
+        assert_eq!(reversed.get_condition_uri(), threshold.get_condition_uri());
+    }
+
+    #[test]
+    fn test_threshold_fulfillment() {
+        let mut threshold = ThresholdSha256::new(2);
+        threshold.add_subfulfillment(signed([1; 32]));
+        threshold.add_subfulfillment(signed([2; 32]));
+
+        let binary = threshold.serialize_binary();
+        assert_eq!(binary[..3], [0xa2, 0x81, 0xd1]);
+        let parsed = ThresholdSha256::parse_binary(&binary, 2).unwrap();
+        assert_eq!(parsed.threshold, 2);
+        assert_eq!(parsed.subfulfillments().count(), 2);
+        assert_eq!(parsed.get_condition_uri(), threshold.get_condition_uri());
+        // more signatures than the threshold, or fewer subconditions
+        assert!(ThresholdSha256::parse_binary(&binary, 1).is_none());
+        assert!(ThresholdSha256::parse_binary(&binary, 3).is_none());
+
+        // a partial fulfillment keeps the subcondition of the missing signature
+        let mut partial = ThresholdSha256::new(2);
+        partial.add_subfulfillment(signed([1; 32]));
+        partial.add_subcondition(signed([2; 32]).get_condition());
+        let parsed = ThresholdSha256::parse_binary(&partial.serialize_binary(), 2).unwrap();
+        assert_eq!(parsed.threshold, 2);
+        assert_eq!(parsed.subfulfillments().count(), 1);
+        assert_eq!(parsed.get_condition_uri(), threshold.get_condition_uri());
+
+        // a 1-of-2 fulfillment writes a single signature
+        let mut one_of_two = ThresholdSha256::new(1);
+        one_of_two.add_subfulfillment(signed([1; 32]));
+        one_of_two.add_subfulfillment(signed([2; 32]));
+        let parsed = ThresholdSha256::parse_binary(&one_of_two.serialize_binary(), 1).unwrap();
+        assert_eq!(parsed.threshold, 1);
+        assert_eq!(parsed.subfulfillments().count(), 1);
+        assert_eq!(parsed.get_condition_uri(), one_of_two.get_condition_uri());
+
+        assert!(ThresholdSha256::parse_binary(&binary[..100], 2).is_none());
+    }
+}
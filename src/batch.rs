@@ -0,0 +1,236 @@
+//! Concurrent submission of many signed transactions.
+//!
+//! A batch is posted in waves. A transaction spending an output of another transaction of the
+//! batch waits until that one has been posted, and its parent is posted in commit mode so the
+//! node knows the output by the time it checks the transfer. Within a wave at most
+//! `concurrency` requests are in flight, and the next transaction is only taken once one of
+//! them completes.
+
+use std::collections::HashMap;
+
+use futures::{stream, StreamExt};
+
+use crate::{
+    connection::{Connection, TRANSACTIONS_ASYNC, TRANSACTIONS_COMMIT, TRANSACTIONS_SYNC},
+    error::Error,
+    transaction::TransactionTemplate,
+};
+
+const DEFAULT_CONCURRENCY: usize = 16;
+
+/// When a post returns, see `Connection::post_transaction_async`, `post_transaction_sync` and
+/// `post_transaction_commit`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PostMode {
+    Async,
+    Sync,
+    Commit,
+}
+
+impl PostMode {
+    pub(crate) fn path(&self) -> &'static str {
+        match self {
+            Self::Async => TRANSACTIONS_ASYNC,
+            Self::Sync => TRANSACTIONS_SYNC,
+            Self::Commit => TRANSACTIONS_COMMIT,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchOptions {
+    pub mode: PostMode,
+    // maximum number of requests in flight
+    pub concurrency: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            mode: PostMode::Commit,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl BatchOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(mut self, mode: PostMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+}
+
+// indices of the transactions of the batch spent by each transaction
+fn dependencies(transactions: &[TransactionTemplate]) -> Vec<Vec<usize>> {
+    let indices: HashMap<&str, usize> = transactions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, tx)| Some((tx.id.as_deref()?, index)))
+        .collect();
+
+    transactions
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            let mut parents: Vec<usize> = tx
+                .inputs
+                .iter()
+                .filter_map(|input| input.fulfills.as_ref()?.get("transaction_id")?.as_str())
+                .filter_map(|id| indices.get(id).copied())
+                .filter(|parent| *parent != index)
+                .collect();
+            parents.sort_unstable();
+            parents.dedup();
+            parents
+        })
+        .collect()
+}
+
+impl<'a> Connection<'a> {
+    /// Post `transactions` and return the outcome of each of them, in the same order.
+    ///
+    /// A transaction whose parent in the batch failed is not posted and fails with
+    /// `Error::DependencyFailed`. A parent already on the ledger still fails with
+    /// `Error::DuplicateTransaction` but its children are posted.
+    pub async fn post_transactions_batch(
+        &self,
+        transactions: Vec<TransactionTemplate>,
+        options: &BatchOptions,
+    ) -> Vec<Result<TransactionTemplate, Error>> {
+        let dependencies = dependencies(&transactions);
+        let ids: Vec<Option<String>> = transactions.iter().map(|tx| tx.id.clone()).collect();
+        let mut has_children = vec![false; transactions.len()];
+        for parents in dependencies.iter() {
+            for parent in parents {
+                has_children[*parent] = true;
+            }
+        }
+
+        let mut results: Vec<Option<Result<TransactionTemplate, Error>>> =
+            (0..transactions.len()).map(|_| None).collect();
+        let mut pending: Vec<Option<TransactionTemplate>> =
+            transactions.into_iter().map(Some).collect();
+
+        loop {
+            let mut wave = Vec::new();
+            for (index, slot) in pending.iter_mut().enumerate() {
+                if slot.is_none() {
+                    continue;
+                }
+
+                let mut ready = true;
+                for parent in dependencies[index].iter() {
+                    match &results[*parent] {
+                        Some(Ok(_)) | Some(Err(Error::DuplicateTransaction { .. })) => {}
+                        Some(Err(_)) => {
+                            *slot = None;
+                            let parent_id = ids[*parent].clone().unwrap_or_default();
+                            results[index] = Some(Err(Error::DependencyFailed(parent_id)));
+                            ready = false;
+                            break;
+                        }
+                        None => ready = false,
+                    }
+                }
+
+                if ready {
+                    let transaction = slot.take().unwrap();
+                    let mode = if has_children[index] {
+                        PostMode::Commit
+                    } else {
+                        options.mode
+                    };
+                    wave.push((index, mode, transaction));
+                }
+            }
+
+            if wave.is_empty() {
+                break;
+            }
+
+            let posted: Vec<(usize, Result<TransactionTemplate, Error>)> = stream::iter(wave)
+                .map(|(index, mode, transaction)| async move {
+                    (index, self.post_transaction(mode.path(), transaction).await)
+                })
+                .buffer_unordered(options.concurrency)
+                .collect()
+                .await;
+            for (index, result) in posted {
+                results[index] = Some(result);
+            }
+        }
+
+        // transactions left pending depend on each other, which content addressed ids rule out
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(Error::InternalError)))
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{fixtures::*, MockNode};
+
+    #[test]
+    fn test_dependencies() {
+        let create = create_transaction(0);
+        let transfer = transfer_transaction(&create, None);
+        let other = create_transaction(1);
+
+        let dependencies = dependencies(&[transfer, other, create]);
+        assert_eq!(dependencies, vec![vec![2], vec![], vec![]]);
+    }
+
+    #[tokio::test]
+    async fn test_post_transactions_batch() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        let create = create_transaction(0);
+        let transfer = transfer_transaction(&create, None);
+        // already on the ledger, so posting it again fails but its child can still be posted
+        let committed = create_transaction(1);
+        node.commit(committed.clone()).unwrap();
+        let child = transfer_transaction(&committed, None);
+
+        let options = BatchOptions::new().mode(PostMode::Async).concurrency(2);
+        let results = conn
+            .post_transactions_batch(
+                vec![
+                    transfer.clone(),
+                    create.clone(),
+                    committed.clone(),
+                    child.clone(),
+                ],
+                &options,
+            )
+            .await;
+
+        assert_eq!(results[0].as_ref().unwrap().id, transfer.id);
+        assert_eq!(results[1].as_ref().unwrap().id, create.id);
+        assert!(matches!(
+            results[2],
+            Err(Error::DuplicateTransaction { retried: false })
+        ));
+        assert_eq!(results[3].as_ref().unwrap().id, child.id);
+
+        let mut ids: Vec<_> = node.transactions().into_iter().map(|tx| tx.id).collect();
+        assert_eq!(ids.remove(0), committed.id);
+        ids.sort();
+        let mut expected = vec![create.id, transfer.id, child.id];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+}
@@ -0,0 +1,367 @@
+//! Synchronous wrapper around `connection::Connection` for code that does not run an async
+//! runtime.
+//!
+//! Each connection drives the async implementation, with the same transport, retries and
+//! failover, on its own single-threaded tokio runtime. Its methods must not be called from
+//! within an async context, where blocking on the runtime would panic.
+
+use std::time::Duration;
+
+use futures::{stream::BoxStream, StreamExt};
+use serde::de::DeserializeOwned;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{
+    batch::{BatchOptions, PostMode},
+    cache::ResponseCache,
+    client::ClientConfig,
+    connection,
+    envelope::SigningEnvelope,
+    error::Error,
+    health::NodeHealth,
+    history::AssetHistory,
+    pagination::PageOptions,
+    rate_limit::RateLimit,
+    request::{NormalizedNode, RequestOption},
+    retry::RetryPolicy,
+    selection::NodeSelector,
+    stream::ValidTransactionEvent,
+    transaction::{
+        AssetSearchResult, Block, MetadataSearchResult, Operation, TransactionOutput,
+        TransactionTemplate, UnspentOutput,
+    },
+};
+
+#[derive(Debug)]
+pub struct Connection<'a> {
+    pub inner: connection::Connection<'a>,
+    runtime: Runtime,
+}
+
+impl<'a> Connection<'a> {
+    /// # Panics
+    ///
+    /// Panics if the tokio runtime cannot be created.
+    pub fn new(nodes: Vec<&'a str>) -> Self {
+        Self::from_async(connection::Connection::new(nodes)).expect("failed to build tokio runtime")
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the tokio runtime cannot be created.
+    pub fn from_nodes(nodes: Vec<NormalizedNode<'a>>) -> Self {
+        Self::from_async(connection::Connection::from_nodes(nodes))
+            .expect("failed to build tokio runtime")
+    }
+
+    pub fn with_client_config(nodes: Vec<&'a str>, config: ClientConfig) -> Result<Self, Error> {
+        Self::from_async(connection::Connection::with_client_config(nodes, config)?)
+    }
+
+    /// Wrap an async connection.
+    pub fn from_async(inner: connection::Connection<'a>) -> Result<Self, Error> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|_| Error::InternalError)?;
+
+        Ok(Self { inner, runtime })
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.inner = self.inner.with_retry_policy(retry_policy);
+        self
+    }
+
+    pub fn with_node_selector(mut self, selector: impl NodeSelector + 'static) -> Self {
+        self.inner = self.inner.with_node_selector(selector);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.inner = self.inner.with_rate_limit(rate_limit);
+        self
+    }
+
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.inner = self.inner.with_cache(cache);
+        self
+    }
+
+    pub fn check_health(&self) {
+        self.runtime.block_on(self.inner.check_health())
+    }
+
+    pub fn node_health(&self) -> Vec<NodeHealth> {
+        self.inner.node_health()
+    }
+
+    pub fn request<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        options: &RequestOption<'_>,
+    ) -> Result<T, Error> {
+        self.runtime.block_on(self.inner.request(path, options))
+    }
+
+    pub fn post_transaction_sync(
+        &self,
+        transaction: TransactionTemplate,
+    ) -> Result<TransactionTemplate, Error> {
+        self.runtime
+            .block_on(self.inner.post_transaction_sync(transaction))
+    }
+
+    pub fn post_transaction_async(
+        &self,
+        transaction: TransactionTemplate,
+    ) -> Result<TransactionTemplate, Error> {
+        self.runtime
+            .block_on(self.inner.post_transaction_async(transaction))
+    }
+
+    pub fn post_transaction_commit(
+        &self,
+        transaction: TransactionTemplate,
+    ) -> Result<TransactionTemplate, Error> {
+        self.runtime
+            .block_on(self.inner.post_transaction_commit(transaction))
+    }
+
+    pub fn post_transactions_batch(
+        &self,
+        transactions: Vec<TransactionTemplate>,
+        options: &BatchOptions,
+    ) -> Vec<Result<TransactionTemplate, Error>> {
+        self.runtime
+            .block_on(self.inner.post_transactions_batch(transactions, options))
+    }
+
+    pub fn list_blocks(&self, transaction_id: &str) -> Result<Vec<u64>, Error> {
+        self.runtime
+            .block_on(self.inner.list_blocks(transaction_id))
+    }
+
+    pub fn get_block(&self, block_height: u64) -> Result<Block, Error> {
+        self.runtime.block_on(self.inner.get_block(block_height))
+    }
+
+    pub fn poll_status_and_fetch_transaction(
+        &self,
+        transaction_id: &str,
+        interval: Duration,
+        deadline: Duration,
+    ) -> Result<TransactionTemplate, Error> {
+        self.runtime
+            .block_on(self.inner.poll_status_and_fetch_transaction(
+                transaction_id,
+                interval,
+                deadline,
+            ))
+    }
+
+    pub fn list_outputs(
+        &self,
+        public_key: &str,
+        spent: Option<bool>,
+    ) -> Result<Vec<TransactionOutput>, Error> {
+        self.runtime
+            .block_on(self.inner.list_outputs(public_key, spent))
+    }
+
+    pub fn get_unspent_outputs(
+        &self,
+        public_key: &str,
+        asset_id: Option<&str>,
+    ) -> Result<Vec<UnspentOutput>, Error> {
+        self.runtime
+            .block_on(self.inner.get_unspent_outputs(public_key, asset_id))
+    }
+
+    pub fn list_transactions(
+        &self,
+        asset_id: &str,
+        operation: Option<Operation>,
+    ) -> Result<Vec<TransactionTemplate>, Error> {
+        self.runtime
+            .block_on(self.inner.list_transactions(asset_id, operation))
+    }
+
+    pub fn get_transaction(&self, transaction_id: &str) -> Result<TransactionTemplate, Error> {
+        self.runtime
+            .block_on(self.inner.get_transaction(transaction_id))
+    }
+
+    pub fn search_assets(
+        &self,
+        search: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<AssetSearchResult>, Error> {
+        self.runtime
+            .block_on(self.inner.search_assets(search, limit))
+    }
+
+    pub fn search_metadata(
+        &self,
+        search: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<MetadataSearchResult>, Error> {
+        self.runtime
+            .block_on(self.inner.search_metadata(search, limit))
+    }
+
+    /// Iterate over the outputs of `public_key`, fetching each page as the previous one runs
+    /// out. See `connection::Connection::outputs_stream`.
+    pub fn outputs_stream(
+        &self,
+        public_key: &str,
+        spent: Option<bool>,
+        options: &PageOptions,
+    ) -> impl Iterator<Item = Result<TransactionOutput, Error>> + '_ {
+        self.iter(self.inner.outputs_stream(public_key, spent, options))
+    }
+
+    /// Iterate over the transactions of the asset `asset_id`. See
+    /// `connection::Connection::transactions_stream`.
+    pub fn transactions_stream(
+        &self,
+        asset_id: &str,
+        operation: Option<Operation>,
+        options: &PageOptions,
+    ) -> impl Iterator<Item = Result<TransactionTemplate, Error>> + '_ {
+        self.iter(self.inner.transactions_stream(asset_id, operation, options))
+    }
+
+    /// Iterate over the outputs of `public_key` along with the transaction holding each of
+    /// them. See `connection::Connection::resolved_outputs_stream`.
+    pub fn resolved_outputs_stream(
+        &self,
+        public_key: &str,
+        spent: Option<bool>,
+        options: &PageOptions,
+    ) -> impl Iterator<Item = Result<UnspentOutput, Error>> + '_ {
+        self.iter(
+            self.inner
+                .resolved_outputs_stream(public_key, spent, options),
+        )
+    }
+
+    pub fn post_envelope(
+        &self,
+        envelope: &SigningEnvelope,
+        mode: PostMode,
+    ) -> Result<TransactionTemplate, Error> {
+        self.runtime
+            .block_on(self.inner.post_envelope(envelope, mode))
+    }
+
+    pub fn asset_history(&self, asset_id: &str) -> Result<AssetHistory, Error> {
+        self.runtime.block_on(self.inner.asset_history(asset_id))
+    }
+
+    /// Iterate over the transactions committed on the connected nodes, blocking until the
+    /// next one arrives. See `connection::Connection::subscribe_valid_transactions`.
+    pub fn subscribe_valid_transactions(
+        &self,
+    ) -> impl Iterator<Item = Result<ValidTransactionEvent, Error>> + '_ {
+        self.iter(self.inner.subscribe_valid_transactions())
+    }
+
+    // Iterate over `stream`, blocking on the runtime for each item.
+    fn iter<'s, T: 's>(&'s self, mut stream: BoxStream<'s, T>) -> impl Iterator<Item = T> + 's {
+        std::iter::from_fn(move || self.runtime.block_on(stream.next()))
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{fixtures::*, MockNode};
+
+    #[test]
+    fn test_blocking_connection() {
+        // the mock node needs a runtime of its own to keep serving between calls
+        let server_runtime = Runtime::new().unwrap();
+        let node = server_runtime.block_on(MockNode::start()).unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        let transaction = create_transaction(0);
+        let transaction_id = transaction.id.clone().unwrap();
+
+        conn.post_transaction_commit(transaction).unwrap();
+
+        assert_eq!(
+            conn.get_transaction(&transaction_id).unwrap().id,
+            Some(transaction_id.clone())
+        );
+        assert_eq!(conn.list_blocks(&transaction_id).unwrap(), vec![1]);
+        assert_eq!(conn.list_outputs(PUBLIC_KEY, Some(false)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_blocking_streams() {
+        let server_runtime = Runtime::new().unwrap();
+        let node = server_runtime.block_on(MockNode::start()).unwrap();
+        let transactions: Vec<TransactionTemplate> = (0..3).map(create_transaction).collect();
+        for tx in transactions.iter() {
+            node.commit(tx.clone()).unwrap();
+        }
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+        let options = PageOptions::new();
+
+        let outputs: Vec<TransactionOutput> = conn
+            .outputs_stream(PUBLIC_KEY, None, &options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(outputs.len(), 3);
+
+        let asset_id = transactions[1].id.clone().unwrap();
+        let asset_transactions: Vec<TransactionTemplate> = conn
+            .transactions_stream(&asset_id, None, &options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(asset_transactions.len(), 1);
+        assert_eq!(asset_transactions[0].id, transactions[1].id);
+
+        let resolved: Vec<UnspentOutput> = conn
+            .resolved_outputs_stream(PUBLIC_KEY, Some(false), &options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let ids: Vec<_> = resolved.iter().map(|output| output.tx.id.clone()).collect();
+        let expected: Vec<_> = transactions.iter().map(|tx| tx.id.clone()).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_blocking_post_envelope_and_asset_history() {
+        let server_runtime = Runtime::new().unwrap();
+        let node = server_runtime.block_on(MockNode::start()).unwrap();
+        let create = create_transaction(0);
+        node.commit(create.clone()).unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        let unspent_output = UnspentOutput {
+            tx: create.clone(),
+            output_index: 0,
+        };
+        let mut envelope =
+            SigningEnvelope::transfer(vec![unspent_output], vec![output(1)], None).unwrap();
+        // an envelope missing its signatures is not posted
+        assert!(conn.post_envelope(&envelope, PostMode::Commit).is_err());
+
+        envelope.sign(&[PRIVATE_KEY.parse().unwrap()]).unwrap();
+        let transfer = conn.post_envelope(&envelope, PostMode::Commit).unwrap();
+
+        let asset_id = create.id.clone().unwrap();
+        let history = conn.asset_history(&asset_id).unwrap();
+        let entries: Vec<_> = history
+            .transactions
+            .iter()
+            .map(|entry| (Some(entry.transaction_id.clone()), entry.height))
+            .collect();
+        assert_eq!(entries, vec![(create.id, Some(1)), (transfer.id, Some(2))]);
+    }
+}
@@ -0,0 +1,295 @@
+//! Client-side cache of immutable ledger objects.
+//!
+//! Committed transactions and blocks never change once the node returns them, so they are
+//! kept in an in-memory LRU and, optionally, in a directory shared between runs. Queries whose
+//! answer changes over time, such as `list_outputs`, are never cached.
+//!
+//! Entries on disk are named after a hash of the endpoints of the connection using the cache, so
+//! connections to different networks can share a directory without reading each other's blocks.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{error::Error, sha256_hash::sha256_hash};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CacheKey {
+    Transaction(String),
+    Block(u64),
+}
+
+impl CacheKey {
+    // name of the file holding the entry, `None` when the key is not safe to use as a path
+    fn file_name(&self, scope: Option<&str>) -> Option<String> {
+        let name = self.base_name()?;
+        Some(match scope {
+            Some(scope) => format!("{scope}-{name}"),
+            None => name,
+        })
+    }
+
+    fn base_name(&self) -> Option<String> {
+        match self {
+            Self::Transaction(id)
+                if !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                Some(format!("tx-{id}.json"))
+            }
+            Self::Transaction(_) => None,
+            Self::Block(height) => Some(format!("block-{height}.json")),
+        }
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transaction(id) => write!(f, "tx:{id}"),
+            Self::Block(height) => write!(f, "block:{height}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Lru {
+    capacity: usize,
+    // serialized value and last use of each key
+    entries: HashMap<CacheKey, (Arc<Vec<u8>>, u64)>,
+    // keys by last use, oldest first
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, key: &CacheKey) -> Option<Arc<Vec<u8>>> {
+        self.tick += 1;
+        let (value, last_use) = self.entries.get_mut(key)?;
+        self.recency.remove(&*last_use);
+        self.recency.insert(self.tick, key.clone());
+        *last_use = self.tick;
+
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, value: Arc<Vec<u8>>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.tick += 1;
+        if let Some((_, last_use)) = self.entries.insert(key.clone(), (value, self.tick)) {
+            self.recency.remove(&last_use);
+        }
+        self.recency.insert(self.tick, key);
+
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+}
+
+/// Cache of committed transactions and blocks, shared by the clones of a `Connection`.
+///
+/// Disk errors are not fatal: an entry that cannot be read or written is fetched from the
+/// node again.
+#[derive(Debug)]
+pub struct ResponseCache {
+    memory: Mutex<Lru>,
+    directory: Option<PathBuf>,
+    // prefix of the files of the entries, identifying the nodes they come from
+    scope: Option<String>,
+}
+
+impl ResponseCache {
+    /// Keep up to `capacity` objects in memory.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            memory: Mutex::new(Lru::new(capacity)),
+            directory: None,
+            scope: None,
+        }
+    }
+
+    /// Keep up to `capacity` objects in memory and every object in `directory`, which is
+    /// created if needed.
+    pub fn with_directory(capacity: usize, directory: impl AsRef<Path>) -> Result<Self, Error> {
+        let directory = directory.as_ref().to_path_buf();
+        std::fs::create_dir_all(&directory)
+            .map_err(|err| Error::CacheError(format!("{}: {err}", directory.display())))?;
+
+        Ok(Self {
+            memory: Mutex::new(Lru::new(capacity)),
+            directory: Some(directory),
+            scope: None,
+        })
+    }
+
+    /// Scope the entries on disk to the nodes at `endpoints`, in any order. Done by
+    /// `Connection::with_cache`.
+    pub fn scoped<S: AsRef<str>>(mut self, endpoints: &[S]) -> Self {
+        let mut endpoints: Vec<&str> = endpoints
+            .iter()
+            .map(|endpoint| endpoint.as_ref().trim_end_matches('/'))
+            .collect();
+        endpoints.sort_unstable();
+        endpoints.dedup();
+
+        let hash = sha256_hash(&endpoints.join("\n"));
+        self.scope = Some(hash[..16].to_string());
+        self
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: &CacheKey) -> Option<T> {
+        let cached = self.memory.lock().unwrap().get(key);
+        if let Some(bytes) = cached {
+            return serde_json::from_slice(&bytes).ok();
+        }
+
+        let path = self.path(key)?;
+        let bytes = tokio::fs::read(path).await.ok()?;
+        let value = serde_json::from_slice(&bytes).ok()?;
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.clone(), Arc::new(bytes));
+
+        Some(value)
+    }
+
+    pub async fn insert<T: Serialize>(&self, key: &CacheKey, value: &T) {
+        let Ok(bytes) = serde_json::to_vec(value) else {
+            return;
+        };
+        let bytes = Arc::new(bytes);
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.clone(), bytes.clone());
+
+        if let Some(path) = self.path(key) {
+            // write to a temporary file first so readers never see a partial entry
+            let tmp = path.with_extension(format!("{:x}.tmp", rand::random::<u32>()));
+            let written = match tokio::fs::write(&tmp, bytes.as_slice()).await {
+                Ok(()) => tokio::fs::rename(&tmp, &path).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = written {
+                tracing::warn!(key = %key, error = %err, "failed to write cache entry");
+            }
+        }
+    }
+
+    /// Drop the entries held in memory. Entries on disk are kept.
+    pub fn clear(&self) {
+        self.memory.lock().unwrap().clear();
+    }
+
+    fn path(&self, key: &CacheKey) -> Option<PathBuf> {
+        Some(
+            self.directory
+                .as_ref()?
+                .join(key.file_name(self.scope.as_deref())?),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(value: &str) -> Arc<Vec<u8>> {
+        Arc::new(value.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut lru = Lru::new(2);
+        lru.insert(CacheKey::Block(1), bytes("1"));
+        lru.insert(CacheKey::Block(2), bytes("2"));
+
+        // using 1 makes 2 the least recently used entry
+        assert!(lru.get(&CacheKey::Block(1)).is_some());
+        lru.insert(CacheKey::Block(3), bytes("3"));
+
+        assert!(lru.get(&CacheKey::Block(1)).is_some());
+        assert!(lru.get(&CacheKey::Block(2)).is_none());
+        assert!(lru.get(&CacheKey::Block(3)).is_some());
+        assert_eq!(lru.entries.len(), lru.recency.len());
+    }
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(CacheKey::Transaction("ab01".into()).to_string(), "tx:ab01");
+        assert_eq!(CacheKey::Block(7).to_string(), "block:7");
+        assert_eq!(
+            CacheKey::Block(7).file_name(None),
+            Some("block-7.json".to_string())
+        );
+        assert_eq!(
+            CacheKey::Block(7).file_name(Some("0123")),
+            Some("0123-block-7.json".to_string())
+        );
+        assert_eq!(CacheKey::Transaction("../etc".into()).file_name(None), None);
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache() {
+        let directory =
+            std::env::temp_dir().join(format!("bigchaindb-cache-{:x}", rand::random::<u64>()));
+        let key = CacheKey::Transaction("ab01".into());
+
+        let cache = ResponseCache::with_directory(0, &directory).unwrap();
+        cache.insert(&key, &vec![1, 2, 3]).await;
+
+        // a new cache over the same directory finds the entry
+        let cache = ResponseCache::with_directory(16, &directory).unwrap();
+        assert_eq!(cache.get::<Vec<u32>>(&key).await, Some(vec![1, 2, 3]));
+        assert_eq!(cache.get::<Vec<u32>>(&CacheKey::Block(1)).await, None);
+
+        // caches of other nodes do not see it, and the order of the endpoints does not matter
+        let scoped = |endpoints: &[&str]| {
+            ResponseCache::with_directory(16, &directory)
+                .unwrap()
+                .scoped(endpoints)
+        };
+        let block = CacheKey::Block(1);
+        scoped(&["http://a/api/v1/", "http://b/api/v1/"])
+            .insert(&block, &vec![4])
+            .await;
+        assert_eq!(
+            scoped(&["http://b/api/v1", "http://a/api/v1/"])
+                .get::<Vec<u32>>(&block)
+                .await,
+            Some(vec![4])
+        );
+        assert_eq!(
+            scoped(&["http://c/api/v1/"]).get::<Vec<u32>>(&block).await,
+            None
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
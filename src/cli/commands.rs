@@ -0,0 +1,824 @@
+//! Implementation of the subcommands.
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use bigchaindb::{
+    batch::PostMode,
+    derivation::{self, DerivationPath},
+    envelope::SigningEnvelope,
+    error::Error,
+    keys::{KeyFormat, PublicKey, SecretKey},
+    transaction::{Output, Transaction, TransactionTemplate, UnspentOutput},
+    Ed25519Keypair,
+};
+use serde_json::Value as JsonValue;
+
+use super::{
+    config::Settings,
+    output::{self, GeneratedKey},
+    AssetsCommand, BlocksCommand, CliError, Command, KeyEncoding, Mode, SearchCommand, SignerArgs,
+    TxCommand,
+};
+#[cfg(feature = "indexer")]
+use super::{IndexArgs, IndexCommand};
+
+/// Output of a transaction, written `TRANSACTION_ID:OUTPUT_INDEX`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputRef {
+    pub transaction_id: String,
+    pub output_index: usize,
+}
+
+impl FromStr for OutputRef {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (transaction_id, output_index) = value
+            .rsplit_once(':')
+            .ok_or_else(|| format!("expected TRANSACTION_ID:OUTPUT_INDEX, got {value}"))?;
+        let output_index = output_index
+            .parse()
+            .map_err(|_| format!("invalid output index {output_index}"))?;
+
+        Ok(Self {
+            transaction_id: transaction_id.to_string(),
+            output_index,
+        })
+    }
+}
+
+/// Recipient of a transfer, written `PUBLIC_KEY[:AMOUNT]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recipient {
+    pub public_key: PublicKey,
+    pub amount: Option<u64>,
+}
+
+impl FromStr for Recipient {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (public_key, amount) = match value.split_once(':') {
+            Some((public_key, amount)) => {
+                let amount = amount
+                    .parse()
+                    .map_err(|_| format!("invalid amount {amount}"))?;
+                (public_key, Some(amount))
+            }
+            None => (value, None),
+        };
+
+        Ok(Self {
+            public_key: public_key.parse().map_err(|err: Error| err.to_string())?,
+            amount,
+        })
+    }
+}
+
+impl From<KeyEncoding> for KeyFormat {
+    fn from(encoding: KeyEncoding) -> Self {
+        match encoding {
+            KeyEncoding::Base58 => Self::Base58,
+            KeyEncoding::Hex => Self::Hex,
+            KeyEncoding::Pem => Self::Pkcs8Pem,
+            KeyEncoding::Jwk => Self::Jwk,
+        }
+    }
+}
+
+impl From<Mode> for PostMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Async => Self::Async,
+            Mode::Sync => Self::Sync,
+            Mode::Commit => Self::Commit,
+        }
+    }
+}
+
+/// Input of `sign` and `post`.
+enum Document {
+    Transaction(TransactionTemplate),
+    Envelope(SigningEnvelope),
+}
+
+// contents of `path`, or of stdin when it is missing or `-`
+fn read_input(path: Option<&Path>) -> Result<String, CliError> {
+    match path {
+        Some(path) if path != Path::new("-") => {
+            fs::read_to_string(path).map_err(|source| CliError::Io {
+                path: path.display().to_string(),
+                source,
+            })
+        }
+        _ => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|source| CliError::Io {
+                    path: String::from("stdin"),
+                    source,
+                })?;
+            Ok(input)
+        }
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: Option<&Path>) -> Result<T, CliError> {
+    let name = path.map_or(String::from("stdin"), |path| path.display().to_string());
+    serde_json::from_str(&read_input(path)?)
+        .map_err(|err| CliError::Usage(format!("{name}: invalid JSON: {err}")))
+}
+
+// an envelope is told apart from a transaction by its signers
+fn read_document(path: Option<&Path>) -> Result<Document, CliError> {
+    let name = path.map_or(String::from("stdin"), |path| path.display().to_string());
+    let text = read_input(path)?;
+    let value: JsonValue = serde_json::from_str(&text)
+        .map_err(|err| CliError::Usage(format!("{name}: invalid JSON: {err}")))?;
+
+    if value.get("signers").is_some() {
+        Ok(Document::Envelope(SigningEnvelope::from_json(&text)?))
+    } else {
+        serde_json::from_value(value)
+            .map(Document::Transaction)
+            .map_err(|err| CliError::Usage(format!("{name}: invalid transaction: {err}")))
+    }
+}
+
+fn is_stdin(path: &Option<impl AsRef<Path>>) -> bool {
+    path.as_ref()
+        .is_some_and(|path| path.as_ref() == Path::new("-"))
+}
+
+// the encoding of a key file is told apart by its first characters
+fn detect_key_format(key: &str) -> KeyFormat {
+    let key = key.trim();
+    if key.starts_with("-----BEGIN") {
+        KeyFormat::Pkcs8Pem
+    } else if key.starts_with('{') {
+        KeyFormat::Jwk
+    } else if key.len() == 64 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+        KeyFormat::Hex
+    } else {
+        KeyFormat::Base58
+    }
+}
+
+fn signing_keys(settings: &Settings, signer: &SignerArgs) -> Result<Vec<SecretKey>, CliError> {
+    let mut keys = Vec::new();
+
+    for path in signer.key_files.iter() {
+        let key = read_input(Some(path))?;
+        keys.push(SecretKey::decode(&key, detect_key_format(&key))?);
+    }
+
+    if !signer.labels.is_empty() {
+        let keystore = settings.open_keystore()?;
+        for label in signer.labels.iter() {
+            keys.push(keystore.secret_key(label)?);
+        }
+    }
+
+    if keys.is_empty() {
+        return Err(CliError::Usage(String::from(
+            "no signing key, pass --key or --key-file",
+        )));
+    }
+    Ok(keys)
+}
+
+/// Sign every input of `transaction` with the key of its owner among `keys`.
+fn sign(
+    transaction: &TransactionTemplate,
+    keys: &[SecretKey],
+) -> Result<TransactionTemplate, CliError> {
+    let public_keys: Vec<String> = keys
+        .iter()
+        .map(|key| key.public_key().to_string())
+        .collect();
+
+    let mut input_keys = Vec::new();
+    for (index, input) in transaction.inputs.iter().enumerate() {
+        let owner = input.owners_before.first().map_or("", String::as_str);
+        let key = public_keys
+            .iter()
+            .position(|public_key| public_key == owner)
+            .ok_or_else(|| {
+                CliError::Usage(format!("no key given for input {index}, owned by {owner}"))
+            })?;
+        input_keys.push(&keys[key]);
+    }
+
+    Ok(Transaction::sign_transaction(transaction, input_keys)?)
+}
+
+fn output_amount(output: &Output) -> Result<u64, CliError> {
+    output
+        .amount
+        .parse()
+        .map_err(|_| CliError::Usage(format!("invalid output amount {}", output.amount)))
+}
+
+pub async fn run(
+    settings: &Settings,
+    command: Command,
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    let format = settings.output;
+
+    match command {
+        Command::Keygen {
+            label,
+            format: encoding,
+            mnemonic,
+            words,
+            path,
+        } => {
+            let (secret_key, mnemonic, path) = if mnemonic {
+                let phrase = derivation::generate_mnemonic(words)?;
+                let path: DerivationPath = path.parse()?;
+                let keypair = Ed25519Keypair::from_mnemonic(&phrase, "", &path)?;
+                (keypair.sk, Some(phrase), Some(path.to_string()))
+            } else {
+                (SecretKey::generate(), None, None)
+            };
+
+            let public_key = secret_key.public_key();
+            // a stored key is not printed
+            let encoded = match &label {
+                Some(label) => {
+                    settings
+                        .open_or_create_keystore()?
+                        .insert(label, &secret_key)?;
+                    None
+                }
+                None => Some(secret_key.encode(encoding.into()).to_string()),
+            };
+
+            output::print(
+                out,
+                format,
+                &GeneratedKey {
+                    public_key: public_key.to_string(),
+                    secret_key: encoded,
+                    mnemonic,
+                    derivation_path: path,
+                    label,
+                },
+            )?;
+        }
+
+        Command::Create {
+            asset,
+            metadata,
+            amount,
+            owner,
+            issuer,
+            unsigned,
+            signer,
+        } => {
+            if is_stdin(&asset) && is_stdin(&metadata) {
+                return Err(CliError::Usage(String::from(
+                    "only one of --asset and --metadata can be read from stdin",
+                )));
+            }
+            let asset: Option<JsonValue> = asset
+                .as_deref()
+                .map(|path| read_json(Some(path)))
+                .transpose()?;
+            let metadata: Option<JsonValue> = metadata
+                .as_deref()
+                .map(|path| read_json(Some(path)))
+                .transpose()?;
+
+            let keys = if unsigned {
+                Vec::new()
+            } else {
+                signing_keys(settings, &signer)?
+            };
+            let issuer = match (keys.as_slice(), issuer) {
+                ([], Some(issuer)) => issuer,
+                ([], None) => {
+                    return Err(CliError::Usage(String::from(
+                        "an unsigned CREATE needs its --issuer",
+                    )))
+                }
+                ([key], None) => key.public_key(),
+                ([key], Some(issuer)) if key.public_key() == issuer => issuer,
+                _ => {
+                    return Err(CliError::Usage(String::from(
+                        "a CREATE is signed by its issuer alone",
+                    )))
+                }
+            };
+
+            let output = Transaction::make_ed25519_output(&owner.unwrap_or(issuer), amount);
+            let transaction = Transaction::make_create_transaction(
+                asset,
+                metadata,
+                vec![output],
+                vec![issuer.to_string()],
+            );
+            if unsigned {
+                output::print(
+                    out,
+                    format,
+                    &SigningEnvelope::new(&transaction, Vec::new())?,
+                )?;
+            } else {
+                output::print(out, format, &sign(&transaction, &keys)?)?;
+            }
+        }
+
+        Command::Transfer {
+            inputs,
+            recipients,
+            metadata,
+            unsigned,
+            signer,
+        } => {
+            let metadata: Option<JsonValue> = metadata
+                .as_deref()
+                .map(|path| read_json(Some(path)))
+                .transpose()?;
+            let conn = settings.connection();
+
+            let mut unspent_outputs = Vec::new();
+            let mut total = 0u64;
+            for input in inputs {
+                let tx = conn.get_transaction(&input.transaction_id).await?;
+                let output = tx.outputs.get(input.output_index).ok_or_else(|| {
+                    CliError::Usage(format!(
+                        "transaction {} has no output {}",
+                        input.transaction_id, input.output_index
+                    ))
+                })?;
+                total = total
+                    .checked_add(output_amount(output)?)
+                    .ok_or_else(|| Error::InvalidAmount(String::from("the inputs overflow")))?;
+                unspent_outputs.push(UnspentOutput {
+                    output_index: input.output_index,
+                    tx,
+                });
+            }
+
+            let asset_id = unspent_outputs[0].tx.asset_id();
+            if unspent_outputs
+                .iter()
+                .any(|unspent| unspent.tx.asset_id() != asset_id)
+            {
+                return Err(CliError::Usage(String::from(
+                    "the inputs of a transfer must hold the same asset",
+                )));
+            }
+
+            let outputs = match recipients.as_slice() {
+                [Recipient {
+                    public_key,
+                    amount: None,
+                }] => vec![Transaction::make_ed25519_output(public_key, total)],
+                _ => {
+                    let mut outputs = Vec::new();
+                    let mut transferred = 0u64;
+                    for recipient in recipients.iter() {
+                        let amount = recipient.amount.ok_or_else(|| {
+                            CliError::Usage(format!(
+                                "no amount for {}, pass PUBLIC_KEY:AMOUNT",
+                                recipient.public_key
+                            ))
+                        })?;
+                        transferred = transferred.checked_add(amount).ok_or_else(|| {
+                            Error::InvalidAmount(String::from("the recipients overflow"))
+                        })?;
+                        outputs.push(Transaction::make_ed25519_output(
+                            &recipient.public_key,
+                            amount,
+                        ));
+                    }
+                    if transferred != total {
+                        return Err(CliError::Usage(format!(
+                            "the recipients get {transferred} shares but the inputs hold {total}"
+                        )));
+                    }
+                    outputs
+                }
+            };
+
+            if unsigned {
+                let envelope = SigningEnvelope::transfer(unspent_outputs, outputs, metadata)?;
+                output::print(out, format, &envelope)?;
+            } else {
+                let transaction =
+                    Transaction::make_transfer_transaction(unspent_outputs, outputs, metadata);
+                output::print(
+                    out,
+                    format,
+                    &sign(&transaction, &signing_keys(settings, &signer)?)?,
+                )?;
+            }
+        }
+
+        Command::Sign { file, signer } => match read_document(file.as_deref())? {
+            Document::Transaction(mut transaction) => {
+                // inputs signed elsewhere keep their fulfillments. The spent outputs are not
+                // known here, so every owner signs; outputs with a lower threshold are spent
+                // through an envelope
+                let keys = signing_keys(settings, &signer)?;
+                let mut signed = 0;
+                for pending in Transaction::missing_signatures(&transaction, &[]) {
+                    if let Some(key) = keys
+                        .iter()
+                        .find(|key| key.public_key().to_string() == pending.public_key)
+                    {
+                        transaction =
+                            Transaction::add_signature(&transaction, &[], pending.input, key)?;
+                        signed += 1;
+                    }
+                }
+                if signed == 0 {
+                    return Err(CliError::Usage(String::from(
+                        "none of the keys owns an unsigned input",
+                    )));
+                }
+                output::print(out, format, &transaction)?;
+            }
+            Document::Envelope(mut envelope) => {
+                if envelope.sign(&signing_keys(settings, &signer)?)? == 0 {
+                    return Err(CliError::Usage(String::from(
+                        "none of the keys is a missing signer of the envelope",
+                    )));
+                }
+                output::print(out, format, &envelope)?;
+            }
+        },
+
+        Command::Merge { files } => {
+            let mut envelopes = Vec::new();
+            let mut transactions = Vec::new();
+            for file in files.iter() {
+                match read_document(Some(file))? {
+                    Document::Envelope(envelope) => envelopes.push(envelope),
+                    Document::Transaction(transaction) => transactions.push(transaction),
+                }
+            }
+
+            match (envelopes.split_first_mut(), transactions.is_empty()) {
+                (Some((envelope, others)), true) => {
+                    for other in others.iter() {
+                        envelope.merge(other)?;
+                    }
+                    output::print(out, format, &*envelope)?;
+                }
+                (None, false) => {
+                    output::print(
+                        out,
+                        format,
+                        &Transaction::combine_signatures(&transactions, &[])?,
+                    )?;
+                }
+                _ => {
+                    return Err(CliError::Usage(String::from(
+                        "envelopes and transactions cannot be merged together",
+                    )))
+                }
+            }
+        }
+
+        Command::Post { file, mode } => {
+            let conn = settings.connection();
+
+            let transaction = match read_document(file.as_deref())? {
+                Document::Envelope(envelope) => conn.post_envelope(&envelope, mode.into()).await?,
+                Document::Transaction(transaction) => match mode {
+                    Mode::Async => conn.post_transaction_async(transaction).await?,
+                    Mode::Sync => conn.post_transaction_sync(transaction).await?,
+                    Mode::Commit => conn.post_transaction_commit(transaction).await?,
+                },
+            };
+            output::print(out, format, &transaction)?;
+        }
+
+        Command::Tx(TxCommand::Get { transaction_id }) => {
+            let transaction = settings
+                .connection()
+                .get_transaction(&transaction_id)
+                .await?;
+            output::print(out, format, &transaction)?;
+        }
+
+        Command::Outputs {
+            public_key,
+            spent,
+            unspent,
+        } => {
+            let spent = match (spent, unspent) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            };
+            let outputs = settings
+                .connection()
+                .list_outputs(&public_key, spent)
+                .await?;
+            output::print(out, format, &outputs)?;
+        }
+
+        Command::Blocks(BlocksCommand::Get { height }) => {
+            let block = settings.connection().get_block(height).await?;
+            output::print(out, format, &block)?;
+        }
+
+        Command::Blocks(BlocksCommand::List { transaction_id }) => {
+            let heights = settings.connection().list_blocks(&transaction_id).await?;
+            output::print(out, format, &heights)?;
+        }
+
+        Command::Assets(AssetsCommand::History {
+            asset_id,
+            dot,
+            height,
+        }) => {
+            let history = settings.connection().asset_history(&asset_id).await?;
+            match height {
+                _ if dot => output::write(out, &history.to_dot())?,
+                Some(height) => output::print(out, format, &history.owners_at(height)?)?,
+                None => output::print(out, format, &history)?,
+            }
+        }
+
+        Command::Assets(AssetsCommand::Search { text, limit }) => {
+            let assets = settings.connection().search_assets(&text, limit).await?;
+            output::print(out, format, &assets)?;
+        }
+
+        Command::Metadata(SearchCommand::Search { text, limit }) => {
+            let metadata = settings.connection().search_metadata(&text, limit).await?;
+            output::print(out, format, &metadata)?;
+        }
+
+        #[cfg(feature = "indexer")]
+        Command::Index(args) => run_index(settings, args, out).await?,
+    }
+
+    Ok(())
+}
+
+// a value given on the command line is JSON, or else a plain string
+#[cfg(feature = "indexer")]
+fn parse_value(value: &str) -> JsonValue {
+    serde_json::from_str(value).unwrap_or_else(|_| JsonValue::String(value.to_string()))
+}
+
+#[cfg(feature = "indexer")]
+async fn run_index(
+    settings: &Settings,
+    args: IndexArgs,
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    use bigchaindb::indexer::{Indexer, OutputQuery};
+
+    let format = settings.output;
+    let mut indexer = Indexer::open(&args.db)?;
+
+    match args.command {
+        IndexCommand::Sync => {
+            let indexed = indexer.sync(&settings.connection()).await?;
+            output::print(
+                out,
+                format,
+                &output::SyncReport {
+                    indexed,
+                    last_height: indexer.last_height()?,
+                },
+            )?;
+        }
+
+        IndexCommand::Follow => indexer.follow(&settings.connection()).await?,
+
+        IndexCommand::Outputs {
+            owner,
+            asset,
+            spent,
+            unspent,
+        } => {
+            let query = OutputQuery {
+                public_key: owner,
+                asset_id: asset,
+                spent: match (spent, unspent) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+            };
+            output::print(out, format, &indexer.outputs(&query)?)?;
+        }
+
+        IndexCommand::Assets { path, value } => {
+            output::print(
+                out,
+                format,
+                &indexer.find_assets(&path, &parse_value(&value))?,
+            )?;
+        }
+
+        IndexCommand::Metadata { path, value } => {
+            output::print(
+                out,
+                format,
+                &indexer.find_metadata(&path, &parse_value(&value))?,
+            )?;
+        }
+
+        IndexCommand::Transactions { since, until } => {
+            let until = until.unwrap_or_else(chrono::Utc::now);
+            output::print(out, format, &indexer.transactions_between(since, until)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_KEY: &str = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX";
+    const PRIVATE_KEY: &str = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT";
+
+    #[test]
+    fn test_parse_references() {
+        let output: OutputRef = "abc:2".parse().unwrap();
+        assert_eq!(output.transaction_id, "abc");
+        assert_eq!(output.output_index, 2);
+        assert!("abc".parse::<OutputRef>().is_err());
+
+        let recipient: Recipient = format!("{PUBLIC_KEY}:5").parse().unwrap();
+        assert_eq!(recipient.amount, Some(5));
+        assert_eq!(PUBLIC_KEY.parse::<Recipient>().unwrap().amount, None);
+        assert!(format!("{PUBLIC_KEY}:x").parse::<Recipient>().is_err());
+    }
+
+    #[cfg(feature = "indexer")]
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("3"), serde_json::json!(3));
+        assert_eq!(parse_value("red"), serde_json::json!("red"));
+        assert_eq!(parse_value("\"3\""), serde_json::json!("3"));
+    }
+
+    #[test]
+    fn test_detect_key_format() {
+        let key: SecretKey = PRIVATE_KEY.parse().unwrap();
+        for format in [
+            KeyFormat::Base58,
+            KeyFormat::Hex,
+            KeyFormat::Pkcs8Pem,
+            KeyFormat::Jwk,
+        ] {
+            assert_eq!(detect_key_format(&key.encode(format)), format);
+        }
+    }
+
+    #[test]
+    fn test_sign_matches_keys_to_inputs() {
+        let transaction = Transaction::make_create_transaction(
+            None,
+            None,
+            vec![Transaction::make_ed25519_output(
+                &PUBLIC_KEY.parse().unwrap(),
+                1,
+            )],
+            vec![PUBLIC_KEY.to_string()],
+        );
+
+        let keys = vec![SecretKey::generate(), PRIVATE_KEY.parse().unwrap()];
+        let signed = sign(&transaction, &keys).unwrap();
+        let expected =
+            Transaction::sign_transaction(&transaction, vec![&PRIVATE_KEY.parse().unwrap()])
+                .unwrap();
+        assert_eq!(signed.id, expected.id);
+
+        assert!(matches!(
+            sign(&transaction, &[SecretKey::generate()]),
+            Err(CliError::Usage(_))
+        ));
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_create_post_get_and_transfer() {
+        use crate::cli::output::OutputFormat;
+        use bigchaindb::mock::MockNode;
+
+        let node = MockNode::start().await.unwrap();
+        let directory =
+            std::env::temp_dir().join(format!("bigchaindb-cli-{:x}", rand::random::<u64>()));
+        fs::create_dir_all(&directory).unwrap();
+        let settings = Settings {
+            nodes: vec![node.endpoint()],
+            headers: Default::default(),
+            keystore: directory.join("keystore.json"),
+            password_file: None,
+            output: OutputFormat::Json,
+        };
+        let key_file = directory.join("key");
+        fs::write(&key_file, PRIVATE_KEY).unwrap();
+        let signer = || SignerArgs {
+            labels: Vec::new(),
+            key_files: vec![key_file.clone()],
+        };
+
+        // run a command and read back the transaction it prints, also saved to `name`
+        let run_command = |command: Command, name: &'static str| {
+            let settings = &settings;
+            let path = directory.join(name);
+            async move {
+                let mut out = Vec::new();
+                run(settings, command, &mut out).await.unwrap();
+                fs::write(&path, &out).unwrap();
+                let transaction: TransactionTemplate = serde_json::from_slice(&out).unwrap();
+                (transaction, path)
+            }
+        };
+
+        let asset = directory.join("asset.json");
+        fs::write(&asset, r#"{ "serial": "A-1" }"#).unwrap();
+        let (create, create_file) = run_command(
+            Command::Create {
+                asset: Some(asset),
+                metadata: None,
+                amount: 3,
+                owner: None,
+                issuer: None,
+                unsigned: false,
+                signer: signer(),
+            },
+            "create.json",
+        )
+        .await;
+        let create_id = create.id.clone().unwrap();
+
+        let (posted, _) = run_command(
+            Command::Post {
+                file: Some(create_file),
+                mode: Mode::Commit,
+            },
+            "posted.json",
+        )
+        .await;
+        assert_eq!(posted.id, create.id);
+
+        let (fetched, _) = run_command(
+            Command::Tx(TxCommand::Get {
+                transaction_id: create_id.clone(),
+            }),
+            "fetched.json",
+        )
+        .await;
+        assert_eq!(
+            fetched.serialize_transaction_into_canonical_string(),
+            create.serialize_transaction_into_canonical_string()
+        );
+
+        let recipient = SecretKey::generate().public_key();
+        let (transfer, transfer_file) = run_command(
+            Command::Transfer {
+                inputs: vec![OutputRef {
+                    transaction_id: create_id.clone(),
+                    output_index: 0,
+                }],
+                recipients: vec![Recipient {
+                    public_key: recipient,
+                    amount: None,
+                }],
+                metadata: None,
+                unsigned: false,
+                signer: signer(),
+            },
+            "transfer.json",
+        )
+        .await;
+        assert_eq!(transfer.asset_id(), Some(create_id));
+        assert_eq!(transfer.outputs[0].amount, "3");
+        assert_eq!(transfer.outputs[0].public_keys, vec![recipient.to_string()]);
+
+        run_command(
+            Command::Post {
+                file: Some(transfer_file),
+                mode: Mode::Sync,
+            },
+            "posted-transfer.json",
+        )
+        .await;
+        let ids: Vec<_> = node.transactions().into_iter().map(|tx| tx.id).collect();
+        assert_eq!(ids, vec![create.id, transfer.id]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+//! Configuration file and the settings of a run.
+//!
+//! The configuration is a TOML file of named profiles:
+//!
+//! ```toml
+//! default_profile = "testnet"
+//!
+//! [profiles.testnet]
+//! nodes = ["https://test.example.com/api/v1/"]
+//! keystore = "/home/ops/.config/bigchaindb/testnet.json"
+//!
+//! [profiles.testnet.headers]
+//! app_id = "..."
+//! app_key = "..."
+//! ```
+//!
+//! Command line arguments override the profile.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
+use bigchaindb::{connection::Connection, keystore::Keystore, request::NormalizedNode};
+use serde::Deserialize;
+
+use super::{output::OutputFormat, CliError, GlobalArgs};
+
+const DEFAULT_NODE: &str = "http://localhost:9984/api/v1/";
+const DEFAULT_PROFILE: &str = "default";
+const PASSWORD_ENV: &str = "BIGCHAINDB_KEYSTORE_PASSWORD";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Profile {
+    #[serde(default)]
+    nodes: Vec<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    keystore: Option<PathBuf>,
+}
+
+/// Nodes, keys and output format of a run, from the profile and the arguments.
+#[derive(Debug)]
+pub struct Settings {
+    pub nodes: Vec<String>,
+    pub headers: BTreeMap<String, String>,
+    pub keystore: PathBuf,
+    pub password_file: Option<PathBuf>,
+    pub output: OutputFormat,
+}
+
+// `$XDG_CONFIG_HOME/bigchaindb` or `~/.config/bigchaindb`
+fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("bigchaindb"))
+}
+
+pub fn parse_header(header: &str) -> Result<(String, String), String> {
+    let (name, value) = header
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got {header}"))?;
+    if name.is_empty() {
+        return Err(format!("empty header name in {header}"));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+fn read_config(path: &Path) -> Result<Config, CliError> {
+    let text = fs::read_to_string(path).map_err(|source| CliError::Io {
+        path: path.display().to_string(),
+        source,
+    })?;
+    toml::from_str(&text).map_err(|err| CliError::Config(format!("{}: {err}", path.display())))
+}
+
+impl Config {
+    // the requested profile, the default one, or an empty profile when there is no default
+    fn profile(&self, name: Option<&str>) -> Result<Profile, CliError> {
+        if let Some(name) = name {
+            return self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| CliError::Config(format!("no profile named {name}")));
+        }
+
+        match &self.default_profile {
+            Some(name) => self.profile(Some(name)),
+            None => Ok(self
+                .profiles
+                .get(DEFAULT_PROFILE)
+                .cloned()
+                .unwrap_or_default()),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(args: &GlobalArgs) -> Result<Self, CliError> {
+        // a missing default file is an empty configuration, a missing explicit one an error
+        let config = match (&args.config, config_dir()) {
+            (Some(path), _) => read_config(path)?,
+            (None, Some(dir)) if dir.join("config.toml").exists() => {
+                read_config(&dir.join("config.toml"))?
+            }
+            (None, _) => Config::default(),
+        };
+
+        Self::from_config(&config, args)
+    }
+
+    fn from_config(config: &Config, args: &GlobalArgs) -> Result<Self, CliError> {
+        let profile = config.profile(args.profile.as_deref())?;
+
+        let nodes = if !args.nodes.is_empty() {
+            args.nodes.clone()
+        } else if !profile.nodes.is_empty() {
+            profile.nodes
+        } else {
+            vec![DEFAULT_NODE.to_string()]
+        };
+
+        let mut headers = profile.headers;
+        headers.extend(args.headers.iter().cloned());
+
+        let keystore = args
+            .keystore
+            .clone()
+            .or(profile.keystore)
+            .or_else(|| config_dir().map(|dir| dir.join("keystore.json")))
+            .ok_or_else(|| CliError::Config(String::from("no keystore, pass --keystore")))?;
+
+        let output = args.output.unwrap_or(if std::io::stdout().is_terminal() {
+            OutputFormat::Human
+        } else {
+            OutputFormat::Json
+        });
+
+        Ok(Self {
+            nodes,
+            headers,
+            keystore,
+            password_file: args.password_file.clone(),
+            output,
+        })
+    }
+
+    pub fn connection(&self) -> Connection<'_> {
+        let headers: HashMap<&str, &str> = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        let headers = (!headers.is_empty()).then_some(headers);
+
+        Connection::from_nodes(
+            self.nodes
+                .iter()
+                .map(|node| NormalizedNode::new(node, headers.clone()))
+                .collect(),
+        )
+    }
+
+    fn password(&self) -> Result<String, CliError> {
+        if let Some(path) = &self.password_file {
+            let password = fs::read_to_string(path).map_err(|source| CliError::Io {
+                path: path.display().to_string(),
+                source,
+            })?;
+            return Ok(password.trim_end_matches(['\r', '\n']).to_string());
+        }
+
+        env::var(PASSWORD_ENV).map_err(|_| {
+            CliError::Config(format!(
+                "the keystore password is read from {PASSWORD_ENV} or --password-file"
+            ))
+        })
+    }
+
+    pub fn open_keystore(&self) -> Result<Keystore, CliError> {
+        Ok(Keystore::open(&self.keystore, &self.password()?)?)
+    }
+
+    /// Open the keystore, creating it if it does not exist yet.
+    pub fn open_or_create_keystore(&self) -> Result<Keystore, CliError> {
+        if self.keystore.exists() {
+            return self.open_keystore();
+        }
+
+        if let Some(dir) = self
+            .keystore
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir).map_err(|source| CliError::Io {
+                path: dir.display().to_string(),
+                source,
+            })?;
+        }
+        Ok(Keystore::create(&self.keystore, &self.password()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(profile: Option<&str>) -> GlobalArgs {
+        GlobalArgs {
+            config: None,
+            profile: profile.map(String::from),
+            nodes: Vec::new(),
+            headers: Vec::new(),
+            keystore: Some(PathBuf::from("keystore.json")),
+            password_file: None,
+            output: Some(OutputFormat::Json),
+        }
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            default_profile = "testnet"
+
+            [profiles.testnet]
+            nodes = ["https://test.example.com/api/v1/"]
+            headers = { app_id = "id", app_key = "key" }
+
+            [profiles.local]
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::from_config(&config, &args(None)).unwrap();
+        assert_eq!(settings.nodes, vec!["https://test.example.com/api/v1/"]);
+        assert_eq!(settings.headers["app_id"], "id");
+
+        let settings = Settings::from_config(&config, &args(Some("local"))).unwrap();
+        assert_eq!(settings.nodes, vec![DEFAULT_NODE]);
+        assert!(settings.headers.is_empty());
+
+        let mut overridden = args(None);
+        overridden.nodes = vec![String::from("http://other/api/v1/")];
+        overridden.headers = vec![parse_header("app_key=other").unwrap()];
+        let settings = Settings::from_config(&config, &overridden).unwrap();
+        assert_eq!(settings.nodes, vec!["http://other/api/v1/"]);
+        assert_eq!(settings.headers["app_key"], "other");
+        assert_eq!(settings.headers["app_id"], "id");
+
+        assert!(Settings::from_config(&config, &args(Some("mainnet"))).is_err());
+        assert!(toml::from_str::<Config>("[profiles.x]\nnode = []").is_err());
+        assert!(parse_header("no-value").is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Arguments of the command line client and their dispatch to `commands`.
+
+mod commands;
+mod config;
+mod output;
+
+use std::{io, path::PathBuf};
+
+use bigchaindb::{derivation::BIGCHAINDB_DERIVATION_PATH, error::Error, keys::PublicKey};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use thiserror::Error;
+
+use self::{
+    commands::{OutputRef, Recipient},
+    config::Settings,
+    output::OutputFormat,
+};
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error(transparent)]
+    Driver(#[from] Error),
+
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{0}")]
+    Config(String),
+
+    #[error("{0}")]
+    Usage(String),
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "bigchaindb",
+    version,
+    about = "Inspect and move assets on BigchainDB nodes"
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Args, Debug)]
+pub struct GlobalArgs {
+    /// Configuration file [default: ~/.config/bigchaindb/config.toml]
+    #[arg(long, global = true, env = "BIGCHAINDB_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Profile of the configuration file to use
+    #[arg(long, short, global = true, env = "BIGCHAINDB_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Node API endpoint replacing the nodes of the profile, may be repeated
+    #[arg(long = "node", global = true)]
+    pub nodes: Vec<String>,
+
+    /// Header sent with every request as NAME=VALUE, may be repeated
+    #[arg(long = "header", global = true, value_parser = config::parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// Keystore holding the keys used with --key
+    #[arg(long, global = true, env = "BIGCHAINDB_KEYSTORE")]
+    pub keystore: Option<PathBuf>,
+
+    /// File holding the keystore password, read from BIGCHAINDB_KEYSTORE_PASSWORD otherwise
+    #[arg(long, global = true)]
+    pub password_file: Option<PathBuf>,
+
+    /// Output format, human on a terminal and JSON otherwise
+    #[arg(long, short, global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+}
+
+/// Keys signing a transaction, matched to its inputs by public key.
+#[derive(Args, Debug, Default)]
+pub struct SignerArgs {
+    /// Label of a keystore key, may be repeated
+    #[arg(long = "key")]
+    pub labels: Vec<String>,
+
+    /// File holding a secret key in base58, hex, PKCS#8 PEM or JWK, may be repeated
+    #[arg(long = "key-file")]
+    pub key_files: Vec<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Generate a keypair, printed or stored in the keystore with --label
+    Keygen {
+        /// Store the key in the keystore under this label instead of printing it
+        #[arg(long)]
+        label: Option<String>,
+
+        /// Encoding of the printed secret key
+        #[arg(long, value_enum, default_value_t = KeyEncoding::Base58)]
+        format: KeyEncoding,
+
+        /// Derive the key from a new BIP39 mnemonic, printed with the key
+        #[arg(long)]
+        mnemonic: bool,
+
+        /// Number of words of the mnemonic
+        #[arg(long, default_value_t = 24, requires = "mnemonic")]
+        words: usize,
+
+        /// Derivation path of the key
+        #[arg(long, default_value = BIGCHAINDB_DERIVATION_PATH, requires = "mnemonic")]
+        path: String,
+    },
+
+    /// Make a CREATE transaction, signed unless --unsigned
+    Create {
+        /// JSON file of the asset data, `-` for stdin
+        #[arg(long)]
+        asset: Option<PathBuf>,
+
+        /// JSON file of the metadata, `-` for stdin
+        #[arg(long)]
+        metadata: Option<PathBuf>,
+
+        /// Number of shares of the asset
+        #[arg(long, default_value_t = 1)]
+        amount: u64,
+
+        /// Owner of the asset, the issuer by default
+        #[arg(long)]
+        owner: Option<PublicKey>,
+
+        /// Issuer of an unsigned transaction, the signing key otherwise
+        #[arg(long)]
+        issuer: Option<PublicKey>,
+
+        /// Print a signing envelope instead, to be signed with `sign`
+        #[arg(long)]
+        unsigned: bool,
+
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+
+    /// Make a TRANSFER transaction of outputs fetched from the nodes
+    Transfer {
+        /// Output to spend as TRANSACTION_ID:OUTPUT_INDEX, may be repeated
+        #[arg(long = "input", required = true)]
+        inputs: Vec<OutputRef>,
+
+        /// Recipient as PUBLIC_KEY[:AMOUNT], may be repeated; a single recipient gets every
+        /// share by default
+        #[arg(long = "to", required = true)]
+        recipients: Vec<Recipient>,
+
+        /// JSON file of the metadata, `-` for stdin
+        #[arg(long)]
+        metadata: Option<PathBuf>,
+
+        /// Print a signing envelope instead, to be signed with `sign`
+        #[arg(long)]
+        unsigned: bool,
+
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+
+    /// Sign the inputs owned by the keys, of a transaction or a signing envelope, without
+    /// contacting any node
+    Sign {
+        /// JSON file of the transaction or envelope, stdin by default
+        file: Option<PathBuf>,
+
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+
+    /// Combine copies of a transaction or signing envelope signed by different parties
+    Merge {
+        /// JSON files of the transactions or envelopes
+        #[arg(required = true, num_args = 2..)]
+        files: Vec<PathBuf>,
+    },
+
+    /// Post a signed transaction, or the transaction of a fully signed envelope
+    Post {
+        /// JSON file of the transaction or envelope, stdin by default
+        file: Option<PathBuf>,
+
+        /// When the node answers
+        #[arg(long, value_enum, default_value_t = Mode::Commit)]
+        mode: Mode,
+    },
+
+    /// Transactions
+    #[command(subcommand)]
+    Tx(TxCommand),
+
+    /// List the outputs of a public key
+    Outputs {
+        public_key: String,
+
+        /// Only spent outputs
+        #[arg(long, conflicts_with = "unspent")]
+        spent: bool,
+
+        /// Only unspent outputs
+        #[arg(long)]
+        unspent: bool,
+    },
+
+    /// Blocks
+    #[command(subcommand)]
+    Blocks(BlocksCommand),
+
+    /// Assets
+    #[command(subcommand)]
+    Assets(AssetsCommand),
+
+    /// Transaction metadata
+    #[command(subcommand)]
+    Metadata(SearchCommand),
+
+    /// Local SQLite index of the ledger
+    #[cfg(feature = "indexer")]
+    Index(IndexArgs),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TxCommand {
+    /// Fetch a committed transaction
+    Get { transaction_id: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BlocksCommand {
+    /// Fetch the block at a height
+    Get { height: u64 },
+
+    /// List the heights of the blocks holding a transaction
+    List { transaction_id: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AssetsCommand {
+    /// Full text search
+    Search {
+        text: String,
+
+        /// Maximum number of results
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+
+    /// Owners of an asset over time, from its CREATE and every TRANSFER
+    History {
+        asset_id: String,
+
+        /// Print a Graphviz graph of the transactions instead
+        #[arg(long)]
+        dot: bool,
+
+        /// Only print the owners at the end of this block
+        #[arg(long, conflicts_with = "dot")]
+        height: Option<u64>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SearchCommand {
+    /// Full text search
+    Search {
+        text: String,
+
+        /// Maximum number of results
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+}
+
+#[cfg(feature = "indexer")]
+#[derive(Args, Debug)]
+pub struct IndexArgs {
+    /// SQLite file of the index, created if missing
+    #[arg(long, env = "BIGCHAINDB_INDEX")]
+    pub db: PathBuf,
+
+    #[command(subcommand)]
+    pub command: IndexCommand,
+}
+
+#[cfg(feature = "indexer")]
+#[derive(Subcommand, Debug)]
+pub enum IndexCommand {
+    /// Store the blocks committed since the last sync
+    Sync,
+
+    /// Sync, then keep storing blocks as they are committed
+    Follow,
+
+    /// Indexed outputs
+    Outputs {
+        /// Only outputs held by this public key
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Only outputs of this asset
+        #[arg(long)]
+        asset: Option<String>,
+
+        /// Only spent outputs
+        #[arg(long, conflicts_with = "unspent")]
+        spent: bool,
+
+        /// Only unspent outputs
+        #[arg(long)]
+        unspent: bool,
+    },
+
+    /// Assets whose data holds a value at a JSON path such as `$.serial`
+    Assets {
+        path: String,
+
+        /// JSON value, or a plain string
+        value: String,
+    },
+
+    /// Transactions whose metadata holds a value at a JSON path
+    Metadata {
+        path: String,
+
+        /// JSON value, or a plain string
+        value: String,
+    },
+
+    /// Transactions of the blocks seen committed by `follow` in a time range
+    Transactions {
+        /// Start of the range, as RFC 3339
+        #[arg(long)]
+        since: chrono::DateTime<chrono::Utc>,
+
+        /// End of the range, as RFC 3339, now by default
+        #[arg(long)]
+        until: Option<chrono::DateTime<chrono::Utc>>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    Async,
+    Sync,
+    Commit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum KeyEncoding {
+    Base58,
+    Hex,
+    Pem,
+    Jwk,
+}
+
+pub async fn run(args: Cli) -> Result<(), CliError> {
+    let settings = Settings::load(&args.global)?;
+    commands::run(&settings, args.command, &mut io::stdout()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn test_cli_arguments() {
+        Cli::command().debug_assert();
+
+        let args = Cli::try_parse_from([
+            "bigchaindb",
+            "transfer",
+            "--input",
+            "abc:1",
+            "--to",
+            "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX:2",
+            "--key",
+            "alice",
+            "-o",
+            "json",
+        ])
+        .unwrap();
+        assert_eq!(args.global.output, Some(OutputFormat::Json));
+        assert!(matches!(
+            args.command,
+            Command::Transfer { ref inputs, ref signer, .. }
+                if inputs[0].output_index == 1 && signer.labels == ["alice"]
+        ));
+
+        assert!(
+            Cli::try_parse_from(["bigchaindb", "outputs", "key", "--spent", "--unspent"]).is_err()
+        );
+        assert!(Cli::try_parse_from(["bigchaindb", "keygen", "--words", "12"]).is_err());
+    }
+}
@@ -0,0 +1,357 @@
+//! JSON and human readable output of the commands.
+
+use std::{collections::BTreeMap, fmt::Write, io};
+
+use bigchaindb::{
+    envelope::SigningEnvelope,
+    history::AssetHistory,
+    transaction::{
+        Asset, AssetSearchResult, Block, MetadataSearchResult, TransactionOutput,
+        TransactionTemplate,
+    },
+};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use super::CliError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Human,
+    Json,
+}
+
+/// Value printed by a command, as JSON or as text for a terminal.
+pub trait Render: Serialize {
+    fn human(&self) -> String;
+}
+
+pub fn print<T: Render>(
+    out: &mut dyn io::Write,
+    format: OutputFormat,
+    value: &T,
+) -> Result<(), CliError> {
+    match format {
+        OutputFormat::Json => write(
+            out,
+            &format!("{}\n", serde_json::to_string_pretty(value).unwrap()),
+        ),
+        OutputFormat::Human => write(out, &value.human()),
+    }
+}
+
+pub fn write(out: &mut dyn io::Write, text: &str) -> Result<(), CliError> {
+    out.write_all(text.as_bytes())
+        .map_err(|source| CliError::Io {
+            path: String::from("stdout"),
+            source,
+        })
+}
+
+fn compact(value: &JsonValue) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+impl Render for TransactionTemplate {
+    fn human(&self) -> String {
+        let mut text = String::new();
+        let operation = self
+            .operation
+            .as_ref()
+            .map_or("-", |operation| operation.as_str());
+        let asset = match &self.asset {
+            Some(Asset::Definition(definition)) => compact(&definition.data),
+            Some(Asset::Link(link)) => link.id.clone(),
+            None => String::from("-"),
+        };
+
+        writeln!(
+            text,
+            "id         {}",
+            self.id.as_deref().unwrap_or("(unsigned)")
+        )
+        .unwrap();
+        writeln!(text, "operation  {operation}").unwrap();
+        writeln!(text, "asset      {asset}").unwrap();
+        writeln!(
+            text,
+            "metadata   {}",
+            self.metadata.as_ref().map_or(String::from("-"), compact)
+        )
+        .unwrap();
+
+        writeln!(text, "inputs").unwrap();
+        for (index, input) in self.inputs.iter().enumerate() {
+            let fulfills = input
+                .fulfills
+                .as_ref()
+                .and_then(|fulfills| {
+                    let transaction_id = fulfills.get("transaction_id")?.as_str()?;
+                    let output_index = fulfills.get("output_index")?;
+                    Some(format!("{transaction_id}:{output_index}"))
+                })
+                .unwrap_or_else(|| String::from("-"));
+            let signed = if input.fulfillment.is_some() {
+                "signed"
+            } else {
+                "unsigned"
+            };
+            writeln!(
+                text,
+                "  {index}  {}  {fulfills}  {signed}",
+                input.owners_before.join(",")
+            )
+            .unwrap();
+        }
+
+        writeln!(text, "outputs").unwrap();
+        for (index, output) in self.outputs.iter().enumerate() {
+            writeln!(
+                text,
+                "  {index}  {}  {}",
+                output.amount,
+                output.public_keys.join(",")
+            )
+            .unwrap();
+        }
+
+        text
+    }
+}
+
+impl Render for SigningEnvelope {
+    fn human(&self) -> String {
+        let mut text = self.transaction().human();
+        let missing = self.missing_signers();
+
+        writeln!(text, "signers").unwrap();
+        for signer in self.signers() {
+            let signed = if missing.contains(&signer) {
+                "missing"
+            } else {
+                "signed"
+            };
+            writeln!(text, "  {}  {}  {signed}", signer.input, signer.public_key).unwrap();
+        }
+
+        text
+    }
+}
+
+impl Render for AssetHistory {
+    fn human(&self) -> String {
+        let mut text = String::new();
+        for entry in self.transactions.iter() {
+            let operation = entry
+                .operation
+                .as_ref()
+                .map_or("-", |operation| operation.as_str());
+            let height = entry
+                .height
+                .map_or(String::from("?"), |height| height.to_string());
+            writeln!(text, "{height}  {operation}  {}", entry.transaction_id).unwrap();
+
+            for holding in self
+                .holdings
+                .iter()
+                .filter(|holding| holding.output.transaction_id == entry.transaction_id)
+            {
+                let spent = match &holding.spent_by {
+                    Some(transaction_id) => format!("spent by {transaction_id}"),
+                    None => String::from("unspent"),
+                };
+                writeln!(
+                    text,
+                    "  {}  {}  {}  {spent}",
+                    holding.output.output_index,
+                    holding.amount,
+                    holding.owners.join(",")
+                )
+                .unwrap();
+            }
+        }
+        text
+    }
+}
+
+/// Amount held by each public key.
+impl Render for BTreeMap<String, u64> {
+    fn human(&self) -> String {
+        self.iter()
+            .map(|(public_key, amount)| format!("{public_key}  {amount}\n"))
+            .collect()
+    }
+}
+
+impl Render for Vec<TransactionOutput> {
+    fn human(&self) -> String {
+        self.iter()
+            .map(|output| format!("{}:{}\n", output.transaction_id, output.output_index))
+            .collect()
+    }
+}
+
+impl Render for Block {
+    fn human(&self) -> String {
+        let mut text = format!("height  {}\n", self.height);
+        for tx in self.transactions.iter() {
+            let operation = tx
+                .operation
+                .as_ref()
+                .map_or("-", |operation| operation.as_str());
+            writeln!(
+                text,
+                "  {}  {operation}",
+                tx.id.as_deref().unwrap_or_default()
+            )
+            .unwrap();
+        }
+        text
+    }
+}
+
+impl Render for Vec<u64> {
+    fn human(&self) -> String {
+        self.iter().map(|height| format!("{height}\n")).collect()
+    }
+}
+
+impl Render for Vec<AssetSearchResult> {
+    fn human(&self) -> String {
+        self.iter()
+            .map(|asset| format!("{}  {}\n", asset.id, compact(&asset.data)))
+            .collect()
+    }
+}
+
+impl Render for Vec<MetadataSearchResult> {
+    fn human(&self) -> String {
+        self.iter()
+            .map(|metadata| format!("{}  {}\n", metadata.id, compact(&metadata.metadata)))
+            .collect()
+    }
+}
+
+impl Render for Vec<TransactionTemplate> {
+    fn human(&self) -> String {
+        self.iter()
+            .map(|tx| {
+                let operation = tx
+                    .operation
+                    .as_ref()
+                    .map_or("-", |operation| operation.as_str());
+                format!("{}  {operation}\n", tx.id.as_deref().unwrap_or_default())
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "indexer")]
+impl Render for Vec<bigchaindb::indexer::IndexedOutput> {
+    fn human(&self) -> String {
+        self.iter()
+            .map(|indexed| {
+                let spent = indexed.spent_by.as_deref().unwrap_or("unspent");
+                format!(
+                    "{}:{}  {}  {}  {spent}\n",
+                    indexed.output.transaction_id,
+                    indexed.output.output_index,
+                    indexed.asset_id,
+                    indexed.amount
+                )
+            })
+            .collect()
+    }
+}
+
+/// Blocks stored by `index sync`.
+#[cfg(feature = "indexer")]
+#[derive(Serialize)]
+pub struct SyncReport {
+    pub indexed: u64,
+    pub last_height: Option<u64>,
+}
+
+#[cfg(feature = "indexer")]
+impl Render for SyncReport {
+    fn human(&self) -> String {
+        let last_height = self
+            .last_height
+            .map_or(String::from("-"), |height| height.to_string());
+        format!(
+            "indexed      {} blocks\nlast height  {last_height}\n",
+            self.indexed
+        )
+    }
+}
+
+/// Keypair made by `keygen`, without the secret key once stored in the keystore.
+#[derive(Serialize)]
+pub struct GeneratedKey {
+    pub public_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub derivation_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Render for GeneratedKey {
+    fn human(&self) -> String {
+        let mut text = format!("public key  {}\n", self.public_key);
+        if let Some(label) = &self.label {
+            writeln!(text, "label       {label}").unwrap();
+        }
+        if let Some(mnemonic) = &self.mnemonic {
+            writeln!(text, "mnemonic    {mnemonic}").unwrap();
+        }
+        if let Some(path) = &self.derivation_path {
+            writeln!(text, "path        {path}").unwrap();
+        }
+        if let Some(secret_key) = &self.secret_key {
+            // a PEM spans several lines
+            if secret_key.contains('\n') {
+                write!(text, "secret key\n{secret_key}").unwrap();
+            } else {
+                writeln!(text, "secret key  {secret_key}").unwrap();
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigchaindb::transaction::Transaction;
+    use serde_json::json;
+
+    use super::*;
+
+    const PUBLIC_KEY: &str = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX";
+
+    #[test]
+    fn test_human_transaction() {
+        let output = Transaction::make_ed25519_output(&PUBLIC_KEY.parse().unwrap(), 3);
+        let transaction = Transaction::make_create_transaction(
+            Some(json!({ "name": "bike" })),
+            None,
+            vec![output],
+            vec![PUBLIC_KEY.to_string()],
+        );
+
+        let text = transaction.human();
+        assert!(text.starts_with("id         (unsigned)\noperation  CREATE\n"));
+        assert!(text.contains("asset      {\"name\":\"bike\"}\n"));
+        assert!(text.contains(&format!("  0  {PUBLIC_KEY}  -  unsigned\n")));
+        assert!(text.ends_with(&format!("outputs\n  0  3  {PUBLIC_KEY}\n")));
+
+        let envelope = SigningEnvelope::new(&transaction, Vec::new()).unwrap();
+        assert!(envelope
+            .human()
+            .ends_with(&format!("signers\n  0  {PUBLIC_KEY}  missing\n")));
+    }
+}
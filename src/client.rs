@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use reqwest::{Certificate, Client, Identity, Proxy};
+
+use crate::error::Error;
+
+const DEFAULT_USER_AGENT: &str = concat!("bigchaindb-rs/", env!("CARGO_PKG_VERSION"));
+
+/// Settings for the `reqwest::Client` shared by every node of a `Transport`.
+///
+/// A single client keeps connections alive between requests, so TCP and TLS handshakes are
+/// only paid once per node instead of once per request.
+#[derive(Clone, Debug, Default)]
+pub struct ClientConfig {
+    pub pool_max_idle_per_host: Option<usize>,
+    pub pool_idle_timeout: Option<Duration>,
+    // PEM encoded certificates trusted in addition to the system roots
+    pub root_certificates: Vec<Vec<u8>>,
+    // PEM encoded client certificate chain and PKCS#8 private key used for mTLS
+    pub identity: Option<(Vec<u8>, Vec<u8>)>,
+    pub proxy: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn root_certificate(mut self, pem: &[u8]) -> Self {
+        self.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    pub fn identity(mut self, certificate_pem: &[u8], private_key_pem: &[u8]) -> Self {
+        self.identity = Some((certificate_pem.to_vec(), private_key_pem.to_vec()));
+        self
+    }
+
+    pub fn proxy(mut self, url: &str) -> Self {
+        self.proxy = Some(url.to_string());
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    pub fn build(&self) -> Result<Client, Error> {
+        let user_agent = self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
+        let mut builder = Client::builder().user_agent(user_agent);
+
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+
+        for pem in self.root_certificates.iter() {
+            let certificate = Certificate::from_pem(pem).map_err(|_| Error::ClientBuildError)?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        if let Some((certificate_pem, private_key_pem)) = &self.identity {
+            let identity = Identity::from_pkcs8_pem(certificate_pem, private_key_pem)
+                .map_err(|_| Error::ClientBuildError)?;
+            builder = builder.identity(identity);
+        }
+
+        if let Some(url) = &self.proxy {
+            let proxy = Proxy::all(url.as_str()).map_err(|_| Error::ClientBuildError)?;
+            builder = builder.proxy(proxy);
+        }
+
+        builder.build().map_err(|_| Error::ClientBuildError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_default_client() {
+        assert!(ClientConfig::new().build().is_ok());
+    }
+
+    #[test]
+    fn test_build_client_with_invalid_proxy() {
+        let config = ClientConfig::new().proxy("not a url");
+        assert!(matches!(config.build(), Err(Error::ClientBuildError)));
+    }
+
+    #[test]
+    fn test_build_client_with_invalid_root_certificate() {
+        let config = ClientConfig::new().root_certificate(b"not a certificate");
+        assert!(matches!(config.build(), Err(Error::ClientBuildError)));
+    }
+}
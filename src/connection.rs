@@ -1,66 +1,282 @@
 #![allow(dead_code)]
 
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::{
+    stream::{self as futures_stream, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::task::JoinHandle;
 
 use crate::{
+    cache::{CacheKey, ResponseCache},
+    client::ClientConfig,
     error::Error,
+    health::NodeHealth,
+    pagination::DEFAULT_CONCURRENCY,
+    rate_limit::RateLimit,
     request::{NormalizedNode, RequestMethod, RequestOption, UrlTemplateSpec},
-    transaction::{TransactionOutput, TransactionTemplate},
+    retry::RetryPolicy,
+    selection::NodeSelector,
+    stream::{self, ValidTransactionEvent},
+    transaction::{
+        AssetSearchResult, Block, MetadataSearchResult, Operation, TransactionOutput,
+        TransactionTemplate, UnspentOutput,
+    },
     transport::Transport,
 };
 
-const BLOCKS: &str = "blocks";
-const BLOCKS_DETAIL: &str = "blocks/%(blockHeight)s";
-const OUTPUTS: &str = "outputs";
-const TRANSACTIONS: &str = "transactions";
-const TRANSACTIONS_SYNC: &str = "transactions?mode=sync";
-const TRANSACTIONS_ASYNC: &str = "transactions?mode=async";
-const TRANSACTIONS_COMMIT: &str = "transactions?mode=commit";
-const TRANSACTIONS_DETAIL: &str = "transactions/{transaction_id}";
-const ASSETS: &str = "assets";
-const METADATA: &str = "metadata";
+pub(crate) const BLOCKS: &str = "blocks";
+pub(crate) const BLOCKS_DETAIL: &str = "blocks/{block_height}";
+pub(crate) const OUTPUTS: &str = "outputs";
+pub(crate) const TRANSACTIONS: &str = "transactions";
+pub(crate) const TRANSACTIONS_SYNC: &str = "transactions?mode=sync";
+pub(crate) const TRANSACTIONS_ASYNC: &str = "transactions?mode=async";
+pub(crate) const TRANSACTIONS_COMMIT: &str = "transactions?mode=commit";
+pub(crate) const TRANSACTIONS_DETAIL: &str = "transactions/{transaction_id}";
+pub(crate) const ASSETS: &str = "assets";
+pub(crate) const METADATA: &str = "metadata";
 
 const DEFAULT_NODE: &str = "http://localhost:9984/api/v1/";
-const DEFAULT_TIMEOUT: u64 = 20; // default timeout is 20 seconds
 
 #[derive(Debug, Clone)]
 pub struct Connection<'a> {
     // Common headers for every request
     pub headers: Option<HashMap<&'a str, &'a str>>,
     pub transport: Transport<'a>,
+    // committed transactions and blocks already fetched, shared between clones
+    pub cache: Option<Arc<ResponseCache>>,
 }
 
 impl<'a> Connection<'a> {
     pub fn new(nodes: Vec<&'a str>) -> Self {
-        let mut normalized_nodes = Vec::new();
-
-        for node in nodes {
-            normalized_nodes.push(NormalizedNode::new(node, None));
+        Self {
+            headers: None,
+            transport: Transport::new(Self::normalize_nodes(nodes), RetryPolicy::default()),
+            cache: None,
         }
+    }
 
+    /// Create a connection to nodes with their own headers and priorities.
+    pub fn from_nodes(nodes: Vec<NormalizedNode<'a>>) -> Self {
         Self {
             headers: None,
-            transport: Transport::new(normalized_nodes, Duration::new(DEFAULT_TIMEOUT, 0)),
+            transport: Transport::new(nodes, RetryPolicy::default()),
+            cache: None,
+        }
+    }
+
+    /// Create a connection whose nodes share one HTTP client built from `config`.
+    pub fn with_client_config(nodes: Vec<&'a str>, config: ClientConfig) -> Result<Self, Error> {
+        let client = config.build()?;
+
+        Ok(Self {
+            headers: None,
+            transport: Transport::with_client(
+                Self::normalize_nodes(nodes),
+                RetryPolicy::default(),
+                client,
+            ),
+            cache: None,
+        })
+    }
+
+    /// Use `retry_policy` for every request that does not set its own.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.transport.retry_policy = retry_policy;
+        self
+    }
+
+    /// Use `selector` to choose the node of each request among the available ones.
+    pub fn with_node_selector(mut self, selector: impl NodeSelector + 'static) -> Self {
+        self.transport.selector = Arc::new(selector);
+        self
+    }
+
+    /// Serve `get_transaction` and `get_block` from `cache` when possible. Its entries on disk
+    /// are scoped to the nodes of the connection.
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        let endpoints: Vec<&str> = self
+            .transport
+            .connection_pool
+            .iter()
+            .map(|connection| connection.node.endpoint)
+            .collect();
+        self.cache = Some(Arc::new(cache.scoped(&endpoints)));
+        self
+    }
+
+    /// Limit the request rate of every node without a rate limit of its own.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        for connection in self.transport.connection_pool.iter_mut() {
+            if connection.node.rate_limit.is_none() {
+                connection.set_rate_limit(rate_limit.clone());
+            }
         }
+        self
+    }
+
+    fn normalize_nodes(nodes: Vec<&'a str>) -> Vec<NormalizedNode<'a>> {
+        nodes
+            .into_iter()
+            .map(|node| NormalizedNode::new(node, None))
+            .collect()
+    }
+
+    /// Probe every node once, see `node_health` for the results.
+    pub async fn check_health(&self) {
+        self.transport.check_health().await
+    }
+
+    /// Health, latency and error statistics of every node.
+    pub fn node_health(&self) -> Vec<NodeHealth> {
+        self.transport.node_health()
+    }
+
+    /// Send a request to `path`, relative to the node API endpoint, with the same failover as
+    /// the built-in endpoints. This is the way to reach endpoints the driver does not wrap.
+    pub async fn request<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        options: &RequestOption<'_>,
+    ) -> Result<T, Error> {
+        self.transport.forward_request(path, options).await
+    }
+
+    /// Post a transaction and return once it has been checked by the node, without waiting for
+    /// it to be committed.
+    pub async fn post_transaction_sync(
+        &self,
+        transaction: TransactionTemplate,
+    ) -> Result<TransactionTemplate, Error> {
+        self.post_transaction(TRANSACTIONS_SYNC, transaction).await
+    }
+
+    /// Post a transaction and return immediately. Use `poll_status_and_fetch_transaction` to
+    /// wait until it is included in a block.
+    pub async fn post_transaction_async(
+        &self,
+        transaction: TransactionTemplate,
+    ) -> Result<TransactionTemplate, Error> {
+        self.post_transaction(TRANSACTIONS_ASYNC, transaction).await
     }
 
+    /// Post a transaction and return once it has been committed to a block.
     pub async fn post_transaction_commit(
-        &mut self,
+        &self,
+        transaction: TransactionTemplate,
+    ) -> Result<TransactionTemplate, Error> {
+        self.post_transaction(TRANSACTIONS_COMMIT, transaction)
+            .await
+    }
+
+    pub(crate) async fn post_transaction(
+        &self,
+        path: &str,
         transaction: TransactionTemplate,
     ) -> Result<TransactionTemplate, Error> {
         let options = RequestOption::new()
             .method(RequestMethod::Post)
-            .body(transaction);
-        let resp: TransactionTemplate = self
+            .json(&transaction)?;
+
+        match self.transport.forward_request(path, &options).await {
+            // A previous attempt posted the transaction before failing. Its id is derived from
+            // its content, so the node already holds this very transaction.
+            Err(Error::DuplicateTransaction { retried: true }) => Ok(transaction),
+            resp => resp,
+        }
+    }
+
+    /// List the heights of the blocks containing the transaction `transaction_id`.
+    pub async fn list_blocks(&self, transaction_id: &str) -> Result<Vec<u64>, Error> {
+        self.list_blocks_with(transaction_id, None).await
+    }
+
+    async fn list_blocks_with(
+        &self,
+        transaction_id: &str,
+        retry_policy: Option<RetryPolicy>,
+    ) -> Result<Vec<u64>, Error> {
+        let mut query = HashMap::new();
+        query.insert("transaction_id", transaction_id);
+
+        let mut options = RequestOption::new().method(RequestMethod::Get).query(query);
+        if let Some(retry_policy) = retry_policy {
+            options = options.retry_policy(retry_policy);
+        }
+
+        let resp: Vec<u64> = self.transport.forward_request(BLOCKS, &options).await?;
+        Ok(resp)
+    }
+
+    pub async fn get_block(&self, block_height: u64) -> Result<Block, Error> {
+        let key = CacheKey::Block(block_height);
+        if let Some(block) = self.cached(&key).await {
+            return Ok(block);
+        }
+
+        let spec = UrlTemplateSpec::new().param("block_height", block_height);
+        let options = RequestOption::new()
+            .method(RequestMethod::Get)
+            .url_template(spec);
+
+        let resp: Block = self
             .transport
-            .forward_request(TRANSACTIONS_COMMIT, &options)
+            .forward_request(BLOCKS_DETAIL, &options)
             .await?;
+        self.store(&key, &resp).await;
         Ok(resp)
     }
 
+    /// Wait until the transaction `transaction_id` is included in a block and return it.
+    ///
+    /// The blocks containing the transaction are polled every `interval`. Errors while polling
+    /// are retried, and `Error::TransactionPollTimeout` is returned if the transaction is still
+    /// not committed after `deadline`. Each request is given no more than the time left before
+    /// `deadline`, so a node that does not answer cannot hold the call past it.
+    pub async fn poll_status_and_fetch_transaction(
+        &self,
+        transaction_id: &str,
+        interval: Duration,
+        deadline: Duration,
+    ) -> Result<TransactionTemplate, Error> {
+        let deadline = Instant::now() + deadline;
+
+        loop {
+            let blocks = self
+                .list_blocks_with(transaction_id, Some(self.retry_policy_until(deadline)))
+                .await;
+            if blocks.is_ok_and(|blocks| !blocks.is_empty()) {
+                return self
+                    .get_transaction_with(transaction_id, Some(self.retry_policy_until(deadline)))
+                    .await;
+            }
+
+            if Instant::now() + interval > deadline {
+                return Err(Error::TransactionPollTimeout(transaction_id.to_string()));
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    // retry policy of the transport, its timeout cut to the time left before `deadline`
+    fn retry_policy_until(&self, deadline: Instant) -> RetryPolicy {
+        let retry_policy = self.transport.retry_policy.clone();
+        let timeout = retry_policy
+            .timeout
+            .min(deadline.saturating_duration_since(Instant::now()));
+        retry_policy.timeout(timeout)
+    }
+
     pub async fn list_outputs(
-        &mut self,
-        public_key: &'a str,
+        &self,
+        public_key: &str,
         spent: Option<bool>,
     ) -> Result<Vec<TransactionOutput>, Error> {
         let mut query = HashMap::new();
@@ -78,21 +294,414 @@ impl<'a> Connection<'a> {
         Ok(resp)
     }
 
+    /// List the unspent outputs of `public_key`, with their transactions, ready to be passed to
+    /// `Transaction::make_transfer_transaction`. With `asset_id`, only outputs of that asset are
+    /// returned.
+    ///
+    /// Each transaction is fetched once, however many outputs it holds, and up to
+    /// `DEFAULT_CONCURRENCY` are fetched at the same time. Outputs not held by `public_key` in
+    /// the fetched transaction are skipped.
+    pub async fn get_unspent_outputs(
+        &self,
+        public_key: &str,
+        asset_id: Option<&str>,
+    ) -> Result<Vec<UnspentOutput>, Error> {
+        let outputs = self.list_outputs(public_key, Some(false)).await?;
+
+        let transaction_ids: HashSet<&str> = outputs
+            .iter()
+            .map(|output| output.transaction_id.as_str())
+            .collect();
+
+        let transactions: HashMap<String, TransactionTemplate> =
+            futures_stream::iter(transaction_ids)
+                .map(|transaction_id| async move {
+                    let tx = self.get_transaction(transaction_id).await?;
+                    Ok::<_, Error>((transaction_id.to_string(), tx))
+                })
+                .buffer_unordered(DEFAULT_CONCURRENCY)
+                .try_collect()
+                .await?;
+
+        let unspent_outputs = outputs
+            .into_iter()
+            .filter_map(|output| {
+                let tx = transactions.get(&output.transaction_id)?;
+                let owned = tx
+                    .outputs
+                    .get(output.output_index)
+                    .is_some_and(|fulfilled| {
+                        fulfilled.public_keys.iter().any(|key| key == public_key)
+                    });
+                let same_asset = match asset_id {
+                    Some(asset_id) => tx.asset_id().as_deref() == Some(asset_id),
+                    None => true,
+                };
+
+                (owned && same_asset).then(|| UnspentOutput {
+                    output_index: output.output_index,
+                    tx: tx.clone(),
+                })
+            })
+            .collect();
+
+        Ok(unspent_outputs)
+    }
+
+    /// List the transactions of the asset `asset_id`, optionally only those of `operation`.
+    pub async fn list_transactions(
+        &self,
+        asset_id: &str,
+        operation: Option<Operation>,
+    ) -> Result<Vec<TransactionTemplate>, Error> {
+        let mut query = HashMap::new();
+        query.insert("asset_id", asset_id);
+
+        if let Some(operation) = &operation {
+            query.insert("operation", operation.as_str());
+        }
+
+        let options = RequestOption::new().method(RequestMethod::Get).query(query);
+
+        let resp: Vec<TransactionTemplate> = self
+            .transport
+            .forward_request(TRANSACTIONS, &options)
+            .await?;
+        Ok(resp)
+    }
+
     pub async fn get_transaction(
-        &mut self,
-        transaction_id: &'a str,
+        &self,
+        transaction_id: &str,
     ) -> Result<TransactionTemplate, Error> {
-        let spec = UrlTemplateSpec {
-            transaction_id: Some(transaction_id),
-        };
-        let options = RequestOption::new()
+        self.get_transaction_with(transaction_id, None).await
+    }
+
+    async fn get_transaction_with(
+        &self,
+        transaction_id: &str,
+        retry_policy: Option<RetryPolicy>,
+    ) -> Result<TransactionTemplate, Error> {
+        let key = CacheKey::Transaction(transaction_id.to_string());
+        // an entry that does not hash to its id was altered on disk, fetch it again
+        if let Some(transaction) = self.cached::<TransactionTemplate>(&key).await {
+            if transaction.id.as_deref() == Some(transaction_id)
+                && transaction.compute_id() == transaction_id
+            {
+                return Ok(transaction);
+            }
+        }
+
+        let spec = UrlTemplateSpec::new().param("transaction_id", transaction_id);
+        let mut options = RequestOption::new()
             .method(RequestMethod::Get)
             .url_template(spec);
+        if let Some(retry_policy) = retry_policy {
+            options = options.retry_policy(retry_policy);
+        }
 
         let resp: TransactionTemplate = self
             .transport
             .forward_request(TRANSACTIONS_DETAIL, &options)
             .await?;
+        self.store(&key, &resp).await;
         Ok(resp)
     }
+
+    /// Search the assets for `search`, a text query of the node's database, returning at most
+    /// `limit` of them.
+    pub async fn search_assets(
+        &self,
+        search: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<AssetSearchResult>, Error> {
+        self.search(ASSETS, search, limit).await
+    }
+
+    /// Search the metadata of the transactions for `search`, see `search_assets`.
+    pub async fn search_metadata(
+        &self,
+        search: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<MetadataSearchResult>, Error> {
+        self.search(METADATA, search, limit).await
+    }
+
+    async fn search<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        search: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<T>, Error> {
+        let limit = limit.map(|limit| limit.to_string());
+        let mut query = HashMap::new();
+        query.insert("search", search);
+        if let Some(limit) = &limit {
+            query.insert("limit", limit.as_str());
+        }
+
+        let options = RequestOption::new().method(RequestMethod::Get).query(query);
+
+        self.transport.forward_request(path, &options).await
+    }
+
+    async fn cached<T: DeserializeOwned>(&self, key: &CacheKey) -> Option<T> {
+        self.cache.as_ref()?.get(key).await
+    }
+
+    // only for objects that never change once returned by a node
+    async fn store<T: Serialize>(&self, key: &CacheKey, value: &T) {
+        if let Some(cache) = &self.cache {
+            cache.insert(key, value).await;
+        }
+    }
+
+    /// Subscribe to the transactions committed on the connected nodes.
+    ///
+    /// Each node is asked for its WebSocket endpoint in its API root, falling back to one derived
+    /// from its API endpoint, and the stream reconnects to the next node whenever the current one
+    /// goes away. A node whose stream does not accept the connection within the timeout of the
+    /// retry policy is marked failed.
+    pub fn subscribe_valid_transactions(
+        &self,
+    ) -> BoxStream<'static, Result<ValidTransactionEvent, Error>> {
+        let nodes = self
+            .transport
+            .connection_pool
+            .iter()
+            .map(|connection| {
+                (
+                    connection.node.endpoint.to_string(),
+                    Some(connection.stats.clone()),
+                )
+            })
+            .collect();
+
+        stream::subscribe_nodes(
+            self.transport.client.clone(),
+            nodes,
+            self.transport.retry_policy.timeout,
+        )
+    }
+}
+
+impl Connection<'static> {
+    /// Probe every node every `interval` in the background until the returned task is aborted.
+    pub fn spawn_health_checks(&self, interval: Duration) -> JoinHandle<()> {
+        let transport = self.transport.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                transport.check_health().await;
+            }
+        })
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{fixtures::*, Fault, MockNode},
+        transaction::Transaction,
+    };
+
+    #[tokio::test]
+    async fn test_post_transaction_sync_and_async() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        let first = create_transaction(0);
+        let posted = conn.post_transaction_sync(first.clone()).await.unwrap();
+        assert_eq!(posted.id, first.id);
+        let second = create_transaction(1);
+        let posted = conn.post_transaction_async(second.clone()).await.unwrap();
+        assert_eq!(posted.id, second.id);
+
+        let ids: Vec<_> = node.transactions().into_iter().map(|tx| tx.id).collect();
+        assert_eq!(ids, vec![first.id.clone(), second.id]);
+
+        // posted once, the node rejects it without the driver retrying
+        assert!(matches!(
+            conn.post_transaction_sync(first).await,
+            Err(Error::DuplicateTransaction { retried: false })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_poll_status_and_fetch_transaction() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+        let transaction = create_transaction(0);
+        let transaction_id = transaction.id.clone().unwrap();
+
+        // committed while the connection polls
+        let poll = conn.poll_status_and_fetch_transaction(
+            &transaction_id,
+            Duration::from_millis(20),
+            Duration::from_secs(5),
+        );
+        let commit = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            node.commit(transaction.clone()).unwrap();
+        };
+        let (polled, ()) = tokio::join!(poll, commit);
+        assert_eq!(polled.unwrap().id, Some(transaction_id));
+
+        let never_committed = create_transaction(1).id.unwrap();
+        let started = Instant::now();
+        let result = conn
+            .poll_status_and_fetch_transaction(
+                &never_committed,
+                Duration::from_millis(20),
+                Duration::from_millis(100),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::TransactionPollTimeout(id)) if id == never_committed
+        ));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // a node that does not answer is given up on at the deadline
+        node.inject_fault(Fault::Timeout(Duration::from_secs(10)));
+        let started = Instant::now();
+        let result = conn
+            .poll_status_and_fetch_transaction(
+                &never_committed,
+                Duration::from_millis(20),
+                Duration::from_millis(200),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::TransactionPollTimeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_search_assets_and_metadata() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        for (name, owner) in [
+            ("Blue bike", "alice"),
+            ("red bike", "bob"),
+            ("car", "alice"),
+        ] {
+            let transaction = Transaction::make_create_transaction(
+                Some(serde_json::json!({ "name": name })),
+                Some(serde_json::json!({ "owner": owner })),
+                vec![output(1)],
+                vec![PUBLIC_KEY.to_string()],
+            );
+            node.commit(sign(&transaction)).unwrap();
+        }
+
+        let assets = conn.search_assets("bike", None).await.unwrap();
+        let names: Vec<_> = assets.iter().map(|asset| &asset.data["name"]).collect();
+        assert_eq!(names, vec!["Blue bike", "red bike"]);
+        assert_eq!(conn.search_assets("BIKE", Some(1)).await.unwrap().len(), 1);
+
+        let metadata = conn.search_metadata("alice", None).await.unwrap();
+        assert_eq!(metadata.len(), 2);
+        let car = conn.search_assets("car", None).await.unwrap();
+        assert_eq!(metadata[1].id, car[0].id);
+    }
+
+    #[tokio::test]
+    async fn test_get_unspent_outputs() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        let create_transactions: Vec<TransactionTemplate> = (0..2)
+            .map(|n| {
+                let transaction = Transaction::make_create_transaction(
+                    Some(serde_json::json!({ "n": n })),
+                    None,
+                    vec![output(1), output(1)],
+                    vec![PUBLIC_KEY.to_string()],
+                );
+                sign(&transaction)
+            })
+            .collect();
+        for tx in create_transactions.iter() {
+            node.commit(tx.clone()).unwrap();
+        }
+
+        let unspent = conn.get_unspent_outputs(PUBLIC_KEY, None).await.unwrap();
+        assert_eq!(unspent.len(), 4);
+
+        // spend the first output of the first asset
+        let transfer_transaction =
+            Transaction::make_transfer_transaction(vec![unspent[0].clone()], vec![output(1)], None);
+        let transfer_transaction = sign(&transfer_transaction);
+        node.commit(transfer_transaction.clone()).unwrap();
+
+        let asset_id = create_transactions[0].id.clone().unwrap();
+        let unspent = conn
+            .get_unspent_outputs(PUBLIC_KEY, Some(&asset_id))
+            .await
+            .unwrap();
+        let mut outputs: Vec<(Option<String>, usize)> = unspent
+            .into_iter()
+            .map(|output| (output.tx.id, output.output_index))
+            .collect();
+        outputs.sort();
+        let mut expected = vec![
+            (create_transactions[0].id.clone(), 1),
+            (transfer_transaction.id.clone(), 0),
+        ];
+        expected.sort();
+        assert_eq!(outputs, expected);
+    }
+
+    #[tokio::test]
+    async fn test_cached_transaction() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()])
+            .with_retry_policy(RetryPolicy::no_retry())
+            .with_cache(ResponseCache::new(16));
+
+        let transaction = create_transaction(0);
+        let transaction_id = transaction.id.clone().unwrap();
+        node.commit(transaction).unwrap();
+
+        conn.get_transaction(&transaction_id).await.unwrap();
+        conn.get_block(1).await.unwrap();
+
+        // served from the cache, so the fault is still pending afterwards
+        node.inject_fault(Fault::InternalServerError);
+        let cached = conn.get_transaction(&transaction_id).await.unwrap();
+        assert_eq!(cached.id, Some(transaction_id));
+        assert_eq!(conn.get_block(1).await.unwrap().height, 1);
+
+        // mutable queries always reach the node
+        assert!(conn.list_outputs(PUBLIC_KEY, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_altered_cache_entry_is_fetched_again() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]).with_cache(ResponseCache::new(16));
+
+        let transaction = create_transaction(0);
+        let transaction_id = transaction.id.clone().unwrap();
+        node.commit(transaction.clone()).unwrap();
+
+        let mut altered = transaction;
+        altered.metadata = Some(serde_json::json!({ "altered": true }));
+        let key = CacheKey::Transaction(transaction_id.clone());
+        conn.cache.as_ref().unwrap().insert(&key, &altered).await;
+
+        let fetched = conn.get_transaction(&transaction_id).await.unwrap();
+        assert_eq!(fetched.metadata, None);
+        assert_eq!(fetched.compute_id(), transaction_id);
+    }
 }
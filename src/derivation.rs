@@ -0,0 +1,218 @@
+//! Deterministic keys from BIP39 mnemonics and SLIP-0010 ed25519 derivation.
+//!
+//! A mnemonic is turned into a 64 bytes seed (BIP39), from which a key is derived along a
+//! path of hardened indices (SLIP-0010). `BIGCHAINDB_DERIVATION_PATH` is the path used by the
+//! JavaScript wallets, so a phrase restores the same keys in both.
+
+use std::{fmt, str::FromStr};
+
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{error::Error, keys::SecretKey, randombytes};
+
+/// `m/44'/822'/0'/0'`, account 0 of coin type 822 (BigchainDB).
+pub const BIGCHAINDB_DERIVATION_PATH: &str = "m/44'/822'/0'/0'";
+
+const HARDENED: u32 = 0x8000_0000;
+const ED25519_CURVE: &[u8] = b"ed25519 seed";
+
+/// Path of hardened indices, e.g. `m/44'/822'/0'/0'`. SLIP-0010 only defines hardened
+/// derivation for ed25519, so every index must be marked with `'` or `h`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    pub fn bigchaindb() -> Self {
+        BIGCHAINDB_DERIVATION_PATH.parse().unwrap()
+    }
+
+    /// Indices of the path, without the hardened bit.
+    pub fn indices(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidDerivationPath(path.to_string());
+
+        let mut segments = path.split('/');
+        if segments.next() != Some("m") {
+            return Err(invalid());
+        }
+
+        segments
+            .map(|segment| {
+                let index = segment
+                    .strip_suffix('\'')
+                    .or_else(|| segment.strip_suffix('h'))
+                    .ok_or_else(invalid)?;
+                index
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|index| *index < HARDENED)
+                    .ok_or_else(invalid)
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("m")?;
+        for index in self.0.iter() {
+            write!(f, "/{index}'")?;
+        }
+        Ok(())
+    }
+}
+
+/// Generate a new mnemonic of `word_count` words, one of 12, 15, 18, 21 or 24.
+pub fn generate_mnemonic(word_count: usize) -> Result<String, Error> {
+    if ![12, 15, 18, 21, 24].contains(&word_count) {
+        return Err(Error::InvalidMnemonic(format!(
+            "unsupported word count {word_count}"
+        )));
+    }
+
+    let mut entropy = Zeroizing::new([0u8; 32]);
+    randombytes(&mut entropy);
+    mnemonic_from_entropy(&entropy[..word_count / 3 * 4])
+}
+
+/// Encode `entropy`, 16 to 32 bytes, as a mnemonic.
+pub fn mnemonic_from_entropy(entropy: &[u8]) -> Result<String, Error> {
+    let mnemonic =
+        Mnemonic::from_entropy(entropy).map_err(|err| Error::InvalidMnemonic(err.to_string()))?;
+    Ok(mnemonic.to_string())
+}
+
+/// BIP39 seed of `phrase`, after checking its words and checksum.
+pub fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> Result<Zeroizing<[u8; 64]>, Error> {
+    let mnemonic =
+        Mnemonic::parse(phrase).map_err(|err| Error::InvalidMnemonic(err.to_string()))?;
+    Ok(Zeroizing::new(mnemonic.to_seed(passphrase)))
+}
+
+// HMAC-SHA512 split into the key and the chain code
+fn hmac_sha512(key: &[u8], data: &[u8]) -> (Zeroizing<[u8; 32]>, Zeroizing<[u8; 32]>) {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    let mut output = mac.finalize().into_bytes();
+
+    let mut secret = Zeroizing::new([0u8; 32]);
+    let mut chain_code = Zeroizing::new([0u8; 32]);
+    secret.copy_from_slice(&output[..32]);
+    chain_code.copy_from_slice(&output[32..]);
+    output.zeroize();
+
+    (secret, chain_code)
+}
+
+/// Derive the ed25519 key of `path` from a BIP39 `seed` following SLIP-0010.
+pub fn derive_secret_key(seed: &[u8], path: &DerivationPath) -> SecretKey {
+    let (mut secret, mut chain_code) = hmac_sha512(ED25519_CURVE, seed);
+
+    for index in path.indices() {
+        let mut data = Zeroizing::new([0u8; 37]);
+        data[1..33].copy_from_slice(&secret[..]);
+        data[33..].copy_from_slice(&(index | HARDENED).to_be_bytes());
+        (secret, chain_code) = hmac_sha512(&chain_code[..], &data[..]);
+    }
+
+    SecretKey::from_bytes(&secret[..]).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ed25519Keypair;
+
+    // https://github.com/satoshilabs/slips/blob/master/slip-0010.md, test vector 1 for ed25519
+    #[test]
+    fn test_slip10_derivation() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+
+        let cases = [
+            (
+                "m",
+                "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+            ),
+            (
+                "m/0'",
+                "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+            ),
+            (
+                "m/0'/1'",
+                "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+            ),
+        ];
+        for (path, expected) in cases {
+            let secret_key = derive_secret_key(&seed, &path.parse().unwrap());
+            assert_eq!(hex::encode(secret_key.as_bytes()), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_derivation_path() {
+        let path = DerivationPath::bigchaindb();
+        assert_eq!(path.indices(), &[44, 822, 0, 0]);
+        assert_eq!(path.to_string(), BIGCHAINDB_DERIVATION_PATH);
+        assert_eq!(
+            "m/44h/822h".parse::<DerivationPath>().unwrap().indices(),
+            &[44, 822]
+        );
+
+        for path in ["", "m/44", "44'/0'", "m/2147483648'", "m/x'"] {
+            assert!(matches!(
+                path.parse::<DerivationPath>(),
+                Err(Error::InvalidDerivationPath(_))
+            ));
+        }
+    }
+
+    // https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+    #[test]
+    fn test_mnemonic() {
+        let phrase = mnemonic_from_entropy(&[0u8; 16]).unwrap();
+        assert_eq!(
+            phrase,
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        assert_eq!(
+            hex::encode(&mnemonic_to_seed(&phrase, "TREZOR").unwrap()[..]),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+
+        assert!(mnemonic_to_seed("abandon abandon", "").is_err());
+        assert_eq!(generate_mnemonic(24).unwrap().split(' ').count(), 24);
+        assert_eq!(generate_mnemonic(12).unwrap().split(' ').count(), 12);
+        assert!(generate_mnemonic(13).is_err());
+    }
+
+    #[test]
+    fn test_keypair_from_mnemonic() {
+        let phrase = generate_mnemonic(24).unwrap();
+        let path = DerivationPath::bigchaindb();
+
+        let keypair = Ed25519Keypair::from_mnemonic(&phrase, "", &path).unwrap();
+        let restored = Ed25519Keypair::from_mnemonic(&phrase, "", &path).unwrap();
+        assert_eq!(keypair.pk, restored.pk);
+        assert_ne!(
+            Ed25519Keypair::from_mnemonic(&phrase, "passphrase", &path)
+                .unwrap()
+                .pk,
+            keypair.pk
+        );
+
+        let from_seed = Ed25519Keypair::from_seed(keypair.sk.as_bytes()).unwrap();
+        assert_eq!(from_seed.pk, keypair.pk);
+        assert!(Ed25519Keypair::from_seed(&[0u8; 16]).is_err());
+    }
+}
@@ -0,0 +1,700 @@
+//! Portable files to sign a transaction on another machine, e.g. an air-gapped one.
+//!
+//! An envelope is a JSON document:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "transaction": { "...": "the transaction, without id nor fulfillments" },
+//!   "unspent_outputs": [{ "output_index": 0, "tx": { "...": "the spent transaction" } }],
+//!   "signers": [{ "input": 0, "public_key": "<base58>" }],
+//!   "signatures": [{ "input": 0, "public_key": "<base58>", "fulfillment": "<uri>" }]
+//! }
+//! ```
+//!
+//! `unspent_outputs` holds the output spent by each input, in the order of the inputs, and is
+//! empty for a `CREATE`. Every spent transaction is checked against its id, so the signing
+//! machine can trust the owners and amounts it shows without asking a node. `signers` lists
+//! the keys each input must be signed with, `signatures` the ones collected so far.
+//!
+//! The envelope is exported where the outputs can be fetched with `SigningEnvelope::new`,
+//! signed offline with `sign`, copies signed by different parties are combined with `merge`,
+//! and `finalize` returns the signed transaction once every signature is there.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::{
+    batch::PostMode,
+    connection::Connection,
+    error::Error,
+    keys::{PublicKey, SecretKey},
+    transaction::{Operation, Output, Transaction, TransactionTemplate, UnspentOutput},
+};
+
+pub const ENVELOPE_VERSION: u32 = 1;
+
+/// Key an input must be signed with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signer {
+    pub input: usize,
+    pub public_key: PublicKey,
+}
+
+/// Fulfillment of an input by one of its signers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputSignature {
+    pub input: usize,
+    pub public_key: PublicKey,
+    pub fulfillment: String,
+}
+
+/// Unsigned transaction with everything needed to check and sign it offline.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SigningEnvelope {
+    version: u32,
+    transaction: TransactionTemplate,
+    #[serde(default)]
+    unspent_outputs: Vec<UnspentOutput>,
+    signers: Vec<Signer>,
+    #[serde(default)]
+    signatures: Vec<InputSignature>,
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidEnvelope(message.into())
+}
+
+fn fulfills(transaction: &TransactionTemplate, index: usize) -> Option<(&str, u64)> {
+    let fulfills = transaction.inputs[index].fulfills.as_ref()?;
+    Some((
+        fulfills.get("transaction_id")?.as_str()?,
+        fulfills.get("output_index")?.as_u64()?,
+    ))
+}
+
+fn total_amount<'a>(outputs: impl Iterator<Item = &'a Output>) -> Result<u64, Error> {
+    outputs
+        .map(|output| {
+            output
+                .amount
+                .parse::<u64>()
+                .map_err(|_| invalid(format!("invalid amount {}", output.amount)))
+        })
+        .sum()
+}
+
+// every output must be locked to its public keys and threshold, as they are what the signer is
+// shown
+fn verify_conditions(transaction: &TransactionTemplate) -> Result<(), Error> {
+    for (index, output) in transaction.outputs.iter().enumerate() {
+        let expected = match output.public_keys.as_slice() {
+            [] => None,
+            [owner] => Transaction::make_ed25519_condition(&owner.parse()?, true),
+            owners => Some(Transaction::make_threshold_condition(
+                &owners
+                    .iter()
+                    .map(|owner| owner.parse())
+                    .collect::<Result<Vec<PublicKey>, Error>>()?,
+                output.threshold(),
+            )?),
+        };
+        let matches = expected.is_some_and(|condition| {
+            serde_json::to_value(condition).ok() == serde_json::to_value(&output.condition).ok()
+        });
+        if !matches {
+            return Err(invalid(format!(
+                "the condition of output {index} does not match its public keys"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// the keys every input must be signed with, each owner of an input signing it
+fn required_signers(transaction: &TransactionTemplate) -> Result<Vec<Signer>, Error> {
+    let mut signers = Vec::new();
+
+    for (input, template) in transaction.inputs.iter().enumerate() {
+        for owner in template.owners_before.iter() {
+            signers.push(Signer {
+                input,
+                public_key: owner.parse()?,
+            });
+        }
+    }
+
+    Ok(signers)
+}
+
+impl SigningEnvelope {
+    /// Envelope of `transaction`, spending `unspent_outputs` in the order of its inputs.
+    pub fn new(
+        transaction: &TransactionTemplate,
+        unspent_outputs: Vec<UnspentOutput>,
+    ) -> Result<Self, Error> {
+        let transaction = transaction.unsigned();
+        let envelope = Self {
+            version: ENVELOPE_VERSION,
+            signers: required_signers(&transaction)?,
+            transaction,
+            unspent_outputs,
+            signatures: Vec::new(),
+        };
+        envelope.verify()?;
+
+        Ok(envelope)
+    }
+
+    /// Envelope of a transfer of `unspent_outputs` to `outputs`, see
+    /// `Transaction::make_transfer_transaction`.
+    pub fn transfer(
+        unspent_outputs: Vec<UnspentOutput>,
+        outputs: Vec<Output>,
+        metadata: Option<JsonValue>,
+    ) -> Result<Self, Error> {
+        if unspent_outputs.is_empty() {
+            return Err(invalid("a transfer spends at least one output"));
+        }
+
+        let transaction =
+            Transaction::make_transfer_transaction(unspent_outputs.clone(), outputs, metadata);
+        Self::new(&transaction, unspent_outputs)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let envelope: Self = serde_json::from_str(json).map_err(|err| invalid(err.to_string()))?;
+        envelope.verify()?;
+        Ok(envelope)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn transaction(&self) -> &TransactionTemplate {
+        &self.transaction
+    }
+
+    pub fn unspent_outputs(&self) -> &[UnspentOutput] {
+        &self.unspent_outputs
+    }
+
+    pub fn signers(&self) -> &[Signer] {
+        &self.signers
+    }
+
+    pub fn signatures(&self) -> &[InputSignature] {
+        &self.signatures
+    }
+
+    /// Signers whose signature is still missing, none for an input once the threshold of the
+    /// output it spends is met.
+    pub fn missing_signers(&self) -> Vec<&Signer> {
+        self.signers
+            .iter()
+            .filter(|signer| {
+                !self.is_signed_by(signer.input, &signer.public_key)
+                    && self.signature_count(signer.input) < self.threshold(signer.input)
+            })
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing_signers().is_empty()
+    }
+
+    // signatures `input` takes, every owner of a CREATE input signing it
+    fn threshold(&self, input: usize) -> usize {
+        self.unspent_outputs
+            .get(input)
+            .and_then(UnspentOutput::output)
+            .map_or_else(
+                || self.transaction.inputs[input].owners_before.len(),
+                Output::threshold,
+            )
+    }
+
+    fn signature_count(&self, input: usize) -> usize {
+        self.signatures
+            .iter()
+            .filter(|signature| signature.input == input)
+            .count()
+    }
+
+    fn is_signed_by(&self, input: usize, public_key: &PublicKey) -> bool {
+        self.signatures
+            .iter()
+            .any(|signature| signature.input == input && signature.public_key == *public_key)
+    }
+
+    /// Check the envelope: the spent transactions against their ids, the inputs against the
+    /// outputs they spend, the conditions of the new outputs against their public keys, the
+    /// amounts, the signers and every signature collected.
+    pub fn verify(&self) -> Result<(), Error> {
+        let transaction = &self.transaction;
+
+        if self.version != ENVELOPE_VERSION {
+            return Err(invalid(format!("unsupported version {}", self.version)));
+        }
+        if transaction.serialize_transaction_into_canonical_string()
+            != transaction
+                .unsigned()
+                .serialize_transaction_into_canonical_string()
+        {
+            return Err(invalid("the transaction is already signed"));
+        }
+
+        match transaction.operation {
+            Some(Operation::CREATE) => {
+                if !self.unspent_outputs.is_empty()
+                    || transaction
+                        .inputs
+                        .iter()
+                        .any(|input| input.fulfills.is_some())
+                {
+                    return Err(invalid("a CREATE spends no output"));
+                }
+            }
+            Some(Operation::TRANSFER) => self.verify_spent_outputs()?,
+            None => return Err(invalid("the transaction has no operation")),
+        }
+        verify_conditions(transaction)?;
+
+        if self.signers != required_signers(transaction)? {
+            return Err(invalid("the signers do not match the owners of the inputs"));
+        }
+
+        for signature in self.signatures.iter() {
+            if !self.signers.contains(&Signer {
+                input: signature.input,
+                public_key: signature.public_key,
+            }) {
+                return Err(invalid(format!(
+                    "{} is not a signer of input {}",
+                    signature.public_key, signature.input
+                )));
+            }
+            if Transaction::verify_input(transaction, signature.input, &signature.fulfillment)?
+                != signature.public_key
+            {
+                return Err(Error::InvalidFulfillment(signature.input));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn verify_spent_outputs(&self) -> Result<(), Error> {
+        let transaction = &self.transaction;
+
+        if self.unspent_outputs.len() != transaction.inputs.len() {
+            return Err(invalid(format!(
+                "{} inputs but {} spent outputs",
+                transaction.inputs.len(),
+                self.unspent_outputs.len()
+            )));
+        }
+
+        let asset_id = transaction.asset_id();
+        for (index, unspent) in self.unspent_outputs.iter().enumerate() {
+            let tx = &unspent.tx;
+            let tx_id = tx.id.as_deref().unwrap_or_default();
+            if tx_id != tx.compute_id() {
+                return Err(invalid(format!(
+                    "transaction {tx_id} does not match its id"
+                )));
+            }
+
+            let output = tx.outputs.get(unspent.output_index).ok_or_else(|| {
+                invalid(format!(
+                    "transaction {tx_id} has no output {}",
+                    unspent.output_index
+                ))
+            })?;
+            if fulfills(transaction, index) != Some((tx_id, unspent.output_index as u64)) {
+                return Err(invalid(format!(
+                    "input {index} does not spend output {tx_id}:{}",
+                    unspent.output_index
+                )));
+            }
+            if transaction.inputs[index].owners_before != output.public_keys {
+                return Err(invalid(format!(
+                    "the owners of input {index} do not hold the output it spends"
+                )));
+            }
+            if tx.asset_id() != asset_id {
+                return Err(invalid(format!("input {index} spends another asset")));
+            }
+        }
+
+        let spent = total_amount(
+            self.unspent_outputs
+                .iter()
+                .map(|unspent| &unspent.tx.outputs[unspent.output_index]),
+        )?;
+        let transferred = total_amount(transaction.outputs.iter())?;
+        if spent != transferred {
+            return Err(invalid(format!(
+                "the outputs hold {transferred} shares but the inputs spend {spent}"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Sign the inputs owned by `keys` that are not signed yet, after checking the envelope,
+    /// and return the number of signatures added.
+    pub fn sign(&mut self, keys: &[SecretKey]) -> Result<usize, Error> {
+        self.verify()?;
+
+        let mut signatures = Vec::new();
+        for signer in self.missing_signers() {
+            if let Some(key) = keys
+                .iter()
+                .find(|key| key.public_key() == signer.public_key)
+            {
+                signatures.push(InputSignature {
+                    input: signer.input,
+                    public_key: signer.public_key,
+                    fulfillment: Transaction::sign_input(&self.transaction, signer.input, key)?,
+                });
+            }
+        }
+
+        let added = signatures.len();
+        self.signatures.extend(signatures);
+        self.signatures.sort_by_key(|signature| signature.input);
+        Ok(added)
+    }
+
+    /// Add the signatures of `other`, a copy of the same envelope signed elsewhere.
+    pub fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        let same_transaction = self
+            .transaction
+            .serialize_transaction_into_canonical_string()
+            == other
+                .transaction
+                .serialize_transaction_into_canonical_string();
+        if !same_transaction || self.signers != other.signers {
+            return Err(invalid("the envelopes hold different transactions"));
+        }
+        other.verify()?;
+
+        for signature in other.signatures.iter() {
+            if !self.is_signed_by(signature.input, &signature.public_key) {
+                self.signatures.push(signature.clone());
+            }
+        }
+        self.signatures.sort_by_key(|signature| signature.input);
+
+        Ok(())
+    }
+
+    /// The signed transaction, ready to be posted, once every signer has signed.
+    pub fn finalize(&self) -> Result<TransactionTemplate, Error> {
+        self.verify()?;
+        if let Some(signer) = self.missing_signers().first() {
+            return Err(Error::MissingSignature {
+                input: signer.input,
+                public_key: signer.public_key.to_string(),
+            });
+        }
+
+        // the signatures of the owners of an input make its fulfillment
+        let spent_outputs: Vec<Output> = self
+            .unspent_outputs
+            .iter()
+            .filter_map(UnspentOutput::output)
+            .cloned()
+            .collect();
+        let mut transaction = self.transaction.clone();
+        for (index, input) in transaction.inputs.iter_mut().enumerate() {
+            let signatures: Vec<String> = self
+                .signatures
+                .iter()
+                .filter(|signature| signature.input == index)
+                .map(|signature| signature.fulfillment.clone())
+                .collect();
+            input.fulfillment = Some(Transaction::fulfill_input(
+                &self.transaction,
+                &spent_outputs,
+                index,
+                &signatures,
+            )?);
+        }
+        transaction.id = Some(transaction.compute_id());
+
+        Ok(transaction)
+    }
+}
+
+impl<'a> Connection<'a> {
+    /// Finalize `envelope` and post its transaction, which is never sent if the envelope does
+    /// not check out.
+    pub async fn post_envelope(
+        &self,
+        envelope: &SigningEnvelope,
+        mode: PostMode,
+    ) -> Result<TransactionTemplate, Error> {
+        let transaction = envelope.finalize()?;
+        self.post_transaction(mode.path(), transaction).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_KEY: &str = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX";
+    const PRIVATE_KEY: &str = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT";
+
+    // an asset of 3 shares, 2 held by `PUBLIC_KEY` and 1 by `other`
+    fn unspent_outputs(other: &SecretKey) -> Vec<UnspentOutput> {
+        let secret_key: SecretKey = PRIVATE_KEY.parse().unwrap();
+        let create = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                Some(serde_json::json!({ "name": "bike" })),
+                None,
+                vec![
+                    Transaction::make_ed25519_output(&secret_key.public_key(), 2),
+                    Transaction::make_ed25519_output(&other.public_key(), 1),
+                ],
+                vec![PUBLIC_KEY.to_string()],
+            ),
+            vec![&secret_key],
+        )
+        .unwrap();
+
+        (0..2)
+            .map(|output_index| UnspentOutput {
+                output_index,
+                tx: create.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sign_offline_and_merge() {
+        let secret_key: SecretKey = PRIVATE_KEY.parse().unwrap();
+        let other = SecretKey::generate();
+        let recipient = SecretKey::generate().public_key();
+
+        let envelope = SigningEnvelope::transfer(
+            unspent_outputs(&other),
+            vec![Transaction::make_ed25519_output(&recipient, 3)],
+            None,
+        )
+        .unwrap();
+        assert_eq!(envelope.missing_signers().len(), 2);
+
+        // each party signs its own copy on its own machine
+        let mut first = SigningEnvelope::from_json(&envelope.to_json()).unwrap();
+        assert_eq!(first.sign(&[secret_key.clone()]).unwrap(), 1);
+        let mut second = SigningEnvelope::from_json(&envelope.to_json()).unwrap();
+        assert_eq!(second.sign(&[other.clone()]).unwrap(), 1);
+        assert!(matches!(
+            first.finalize(),
+            Err(Error::MissingSignature { input: 1, .. })
+        ));
+
+        first.merge(&second).unwrap();
+        assert!(first.is_complete());
+        let signed = first.finalize().unwrap();
+
+        let expected =
+            Transaction::sign_transaction(envelope.transaction(), vec![&secret_key, &other])
+                .unwrap();
+        assert_eq!(signed.id, expected.id);
+        assert_eq!(signed.inputs[1].fulfillment, expected.inputs[1].fulfillment);
+    }
+
+    #[test]
+    fn test_tampered_envelope_is_rejected() {
+        let other = SecretKey::generate();
+        let recipient = SecretKey::generate().public_key();
+        let envelope = SigningEnvelope::transfer(
+            unspent_outputs(&other),
+            vec![Transaction::make_ed25519_output(&recipient, 3)],
+            None,
+        )
+        .unwrap();
+
+        // the spent output claims more shares than were issued
+        let mut json: JsonValue = serde_json::from_str(&envelope.to_json()).unwrap();
+        json["unspent_outputs"][0]["tx"]["outputs"][0]["amount"] = "20".into();
+        assert!(matches!(
+            SigningEnvelope::from_json(&json.to_string()),
+            Err(Error::InvalidEnvelope(_))
+        ));
+
+        // the recipients would get more than is spent
+        assert!(SigningEnvelope::transfer(
+            unspent_outputs(&other),
+            vec![Transaction::make_ed25519_output(&recipient, 4)],
+            None,
+        )
+        .is_err());
+
+        // a signature of another input is not accepted
+        let mut signed = envelope.clone();
+        signed.sign(&[PRIVATE_KEY.parse().unwrap()]).unwrap();
+        let mut json: JsonValue = serde_json::from_str(&signed.to_json()).unwrap();
+        json["signatures"][0]["input"] = 1.into();
+        json["signatures"][0]["public_key"] = other.public_key().to_string().into();
+        assert!(SigningEnvelope::from_json(&json.to_string()).is_err());
+
+        // an output shown to the signer as going to `recipient` but locked to another key
+        let thief = SecretKey::generate().public_key().to_string();
+        let mut json: JsonValue = serde_json::from_str(&envelope.to_json()).unwrap();
+        json["transaction"]["outputs"][0]["condition"]["details"]["public_key"] =
+            thief.clone().into();
+        assert!(matches!(
+            SigningEnvelope::from_json(&json.to_string()),
+            Err(Error::InvalidEnvelope(_))
+        ));
+
+        // or to the condition of another key under the details of `recipient`
+        let mut json: JsonValue = serde_json::from_str(&envelope.to_json()).unwrap();
+        json["transaction"]["outputs"][0]["condition"]["uri"] =
+            Transaction::make_ed25519_output(&thief.parse().unwrap(), 3)
+                .condition
+                .uri
+                .into();
+        assert!(matches!(
+            SigningEnvelope::from_json(&json.to_string()),
+            Err(Error::InvalidEnvelope(_))
+        ));
+    }
+
+    #[test]
+    fn test_create_envelope() {
+        let secret_key: SecretKey = PRIVATE_KEY.parse().unwrap();
+        let transaction = Transaction::make_create_transaction(
+            None,
+            None,
+            vec![Transaction::make_ed25519_output(
+                &secret_key.public_key(),
+                1,
+            )],
+            vec![PUBLIC_KEY.to_string()],
+        );
+
+        let mut envelope = SigningEnvelope::new(&transaction, Vec::new()).unwrap();
+        assert!(SigningEnvelope::new(&transaction, unspent_outputs(&secret_key)).is_err());
+        envelope.sign(&[secret_key.clone()]).unwrap();
+        assert_eq!(
+            envelope.finalize().unwrap().id,
+            Transaction::sign_transaction(&transaction, vec![&secret_key])
+                .unwrap()
+                .id
+        );
+    }
+
+    #[test]
+    fn test_threshold_envelope() {
+        let secret_key: SecretKey = PRIVATE_KEY.parse().unwrap();
+        let other = SecretKey::generate();
+        let shared = Transaction::make_output(
+            Transaction::make_threshold_condition(
+                &[secret_key.public_key(), other.public_key()],
+                2,
+            )
+            .unwrap(),
+            String::from("2"),
+        );
+        let create = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                None,
+                None,
+                vec![shared],
+                vec![PUBLIC_KEY.to_string()],
+            ),
+            vec![&secret_key],
+        )
+        .unwrap();
+        assert!(SigningEnvelope::new(&create, Vec::new()).is_ok());
+
+        let recipient = SecretKey::generate().public_key();
+        let envelope = SigningEnvelope::transfer(
+            vec![UnspentOutput {
+                output_index: 0,
+                tx: create,
+            }],
+            vec![Transaction::make_ed25519_output(&recipient, 2)],
+            None,
+        )
+        .unwrap();
+        assert_eq!(envelope.signers().len(), 2);
+
+        // both owners of the spent output sign the same input
+        let mut first = envelope.clone();
+        first.sign(&[secret_key.clone()]).unwrap();
+        let mut second = envelope.clone();
+        second.sign(&[other.clone()]).unwrap();
+        assert!(matches!(
+            first.finalize(),
+            Err(Error::MissingSignature { input: 0, .. })
+        ));
+        first.merge(&second).unwrap();
+        let signed = first.finalize().unwrap();
+
+        let expected = Transaction::add_signature(envelope.transaction(), &[], 0, &secret_key)
+            .and_then(|transaction| Transaction::add_signature(&transaction, &[], 0, &other))
+            .unwrap();
+        assert_eq!(signed.inputs[0].fulfillment, expected.inputs[0].fulfillment);
+        assert_eq!(signed.id, expected.id);
+    }
+
+    #[test]
+    fn test_m_of_n_envelope() {
+        let secret_key: SecretKey = PRIVATE_KEY.parse().unwrap();
+        let keys: Vec<SecretKey> = (0..3).map(|_| SecretKey::generate()).collect();
+        let owners: Vec<PublicKey> = keys.iter().map(SecretKey::public_key).collect();
+        let shared = Transaction::make_output(
+            Transaction::make_threshold_condition(&owners, 2).unwrap(),
+            String::from("1"),
+        );
+        let create = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                None,
+                None,
+                vec![shared.clone()],
+                vec![PUBLIC_KEY.to_string()],
+            ),
+            vec![&secret_key],
+        )
+        .unwrap();
+
+        let mut envelope = SigningEnvelope::transfer(
+            vec![UnspentOutput {
+                output_index: 0,
+                tx: create,
+            }],
+            vec![Transaction::make_ed25519_output(
+                &secret_key.public_key(),
+                1,
+            )],
+            None,
+        )
+        .unwrap();
+        assert_eq!(envelope.signers().len(), 3);
+
+        envelope.sign(&[keys[0].clone()]).unwrap();
+        assert_eq!(envelope.missing_signers().len(), 2);
+        envelope.sign(&[keys[1].clone()]).unwrap();
+        assert!(envelope.is_complete());
+        assert_eq!(envelope.sign(&[keys[2].clone()]).unwrap(), 0);
+
+        let signed = envelope.finalize().unwrap();
+        let expected =
+            Transaction::add_signature(envelope.transaction(), &[shared.clone()], 0, &keys[0])
+                .and_then(|transaction| {
+                    Transaction::add_signature(&transaction, &[shared], 0, &keys[1])
+                })
+                .unwrap();
+        assert_eq!(signed.inputs[0].fulfillment, expected.inputs[0].fulfillment);
+        assert_eq!(signed.id, expected.id);
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,9 +16,100 @@ pub enum Error {
     #[error("internal error")]
     InternalError,
 
-    #[error("HTTP Error: Requested page not reachable")]
-    ResponseError,
+    #[error("HTTP Error: Requested page not reachable ({status}: {message})")]
+    ResponseError { status: u16, message: String },
+
+    #[error("transaction already exists")]
+    DuplicateTransaction {
+        // whether the request was retried, in which case an earlier attempt may have posted it
+        retried: bool,
+    },
+
+    #[error("rate limited by the node")]
+    RateLimited {
+        // delay requested by the `Retry-After` header of the response
+        retry_after: Option<Duration>,
+    },
+
+    // the request would have to wait past its deadline for the local rate limit of the node
+    #[error("rate limit of {0} reached, no request left before the deadline")]
+    RateLimitExceeded(String),
 
     #[error("serde_json error")]
     SerdeError,
+
+    #[error("failed to build HTTP client")]
+    ClientBuildError,
+
+    #[error("transaction {0} was not committed before the deadline")]
+    TransactionPollTimeout(String),
+
+    #[error("invalid URL template: {0}")]
+    InvalidUrlTemplate(String),
+
+    #[error("missing URL parameter: {0}")]
+    MissingUrlParameter(String),
+
+    #[error("invalid value for URL parameter: {0}")]
+    InvalidUrlParameter(String),
+
+    #[error("cache error: {0}")]
+    CacheError(String),
+
+    #[error("dependency {0} was not posted")]
+    DependencyFailed(String),
+
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+
+    #[error("invalid derivation path: {0}")]
+    InvalidDerivationPath(String),
+
+    #[error("keystore error: {0}")]
+    KeystoreError(String),
+
+    #[error("invalid keystore password")]
+    InvalidPassword,
+
+    #[error("no key labeled {0}")]
+    KeyNotFound(String),
+
+    #[error("invalid fulfillment for input {0}")]
+    InvalidFulfillment(usize),
+
+    #[error("invalid signing envelope: {0}")]
+    InvalidEnvelope(String),
+
+    #[error("input {input} is not signed by {public_key}")]
+    MissingSignature { input: usize, public_key: String },
+
+    #[error("no input {0}")]
+    NoSuchInput(usize),
+
+    #[error("{public_key} does not own input {input}")]
+    NotAnOwner { input: usize, public_key: String },
+
+    #[error("the partially signed transactions differ")]
+    TransactionMismatch,
+
+    #[error("a threshold of {threshold} cannot be met by {keys} keys")]
+    InvalidThreshold { threshold: usize, keys: usize },
+
+    #[error("invalid amount: {0}")]
+    InvalidAmount(String),
+
+    #[error(
+        "insufficient funds of asset {asset_id}: {requested} requested, {available} available"
+    )]
+    InsufficientFunds {
+        asset_id: String,
+        requested: u64,
+        available: u64,
+    },
+
+    #[error("indexer error: {0}")]
+    IndexerError(String),
 }
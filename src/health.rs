@@ -0,0 +1,86 @@
+use std::time::{Duration, Instant};
+
+// weight of the latest sample in the moving averages
+const EWMA_ALPHA: f64 = 0.2;
+
+/// Latency and error statistics of a node, updated by requests and health probes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeStats {
+    // exponentially weighted moving average of the response time
+    pub latency: Option<Duration>,
+    // exponentially weighted moving average of failures, in [0, 1]
+    pub error_rate: f64,
+    pub requests: u64,
+    pub failures: u64,
+    // result of the last health probe, nodes are assumed healthy until probed
+    pub healthy: bool,
+    pub last_probe: Option<Instant>,
+}
+
+impl Default for NodeStats {
+    fn default() -> Self {
+        Self {
+            latency: None,
+            error_rate: 0.0,
+            requests: 0,
+            failures: 0,
+            healthy: true,
+            last_probe: None,
+        }
+    }
+}
+
+impl NodeStats {
+    pub fn record_success(&mut self, latency: Duration) {
+        self.requests += 1;
+        self.error_rate *= 1.0 - EWMA_ALPHA;
+        self.latency = Some(match self.latency {
+            Some(average) => average.mul_f64(1.0 - EWMA_ALPHA) + latency.mul_f64(EWMA_ALPHA),
+            None => latency,
+        });
+    }
+
+    pub fn record_failure(&mut self) {
+        self.requests += 1;
+        self.failures += 1;
+        self.error_rate = self.error_rate * (1.0 - EWMA_ALPHA) + EWMA_ALPHA;
+    }
+}
+
+/// Snapshot of the state of a node, as reported by `Connection::node_health`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeHealth {
+    pub endpoint: String,
+    pub priority: u32,
+    pub healthy: bool,
+    pub latency: Option<Duration>,
+    pub error_rate: f64,
+    pub requests: u64,
+    pub failures: u64,
+    // time left before the node is used again after a failure
+    pub backoff: Option<Duration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_stats_ewma() {
+        let mut stats = NodeStats::default();
+
+        stats.record_success(Duration::from_millis(100));
+        assert_eq!(stats.latency, Some(Duration::from_millis(100)));
+
+        stats.record_success(Duration::from_millis(200));
+        assert_eq!(stats.latency, Some(Duration::from_millis(120)));
+
+        stats.record_failure();
+        assert!((stats.error_rate - 0.2).abs() < 1e-9);
+        stats.record_success(Duration::from_millis(120));
+        assert!((stats.error_rate - 0.16).abs() < 1e-9);
+
+        assert_eq!(stats.requests, 4);
+        assert_eq!(stats.failures, 1);
+    }
+}
@@ -0,0 +1,371 @@
+//! Ownership history of an asset, rebuilt from its transactions.
+//!
+//! `Connection::asset_history` fetches the CREATE and every TRANSFER of an asset with the
+//! height of the block holding each of them. Every output of these transactions becomes a
+//! `Holding`, created at the height of its transaction and, once spent, closed at the height of
+//! the transaction spending it. Holdings tell who held the asset at a given height and, followed
+//! backwards through the inputs, the chain of custody of an output.
+
+use std::collections::{BTreeMap, HashSet};
+
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    connection::Connection,
+    error::Error,
+    pagination::DEFAULT_CONCURRENCY,
+    transaction::{Operation, TransactionOutput, TransactionTemplate},
+};
+
+/// Transaction of the asset and the block holding it.
+#[derive(Clone, Debug, Serialize)]
+pub struct HistoryEntry {
+    pub transaction_id: String,
+    pub operation: Option<Operation>,
+    // `None` when the node does not know the block yet
+    pub height: Option<u64>,
+    pub spends: Vec<TransactionOutput>,
+    pub metadata: Option<JsonValue>,
+}
+
+/// Output of the asset, from the block creating it to the one spending it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Holding {
+    pub output: TransactionOutput,
+    pub owners: Vec<String>,
+    pub amount: String,
+    pub height: Option<u64>,
+    pub spent_by: Option<String>,
+    pub spent_at: Option<u64>,
+}
+
+impl Holding {
+    /// Whether the output was held at the end of the block `height`.
+    pub fn is_held_at(&self, height: u64) -> bool {
+        self.height.is_some_and(|created| created <= height)
+            && !self.spent_at.is_some_and(|spent| spent <= height)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AssetHistory {
+    pub asset_id: String,
+    // in the order of the blocks
+    pub transactions: Vec<HistoryEntry>,
+    pub holdings: Vec<Holding>,
+}
+
+fn fulfills(transaction: &TransactionTemplate) -> Vec<TransactionOutput> {
+    transaction
+        .inputs
+        .iter()
+        .filter_map(|input| serde_json::from_value(input.fulfills.clone()?).ok())
+        .collect()
+}
+
+// first characters of an id or a public key, enough to tell them apart in a graph
+fn short(value: &str) -> &str {
+    value.get(..8).unwrap_or(value)
+}
+
+// `value` inside a quoted DOT string
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl AssetHistory {
+    /// History of `asset_id` from its transactions and the height of each of them.
+    pub fn from_transactions(
+        asset_id: &str,
+        mut transactions: Vec<(TransactionTemplate, Option<u64>)>,
+    ) -> Self {
+        // transactions of unknown height come last
+        transactions.sort_by_key(|(_, height)| height.unwrap_or(u64::MAX));
+
+        let mut entries = Vec::new();
+        let mut holdings = Vec::new();
+        for (transaction, height) in transactions.iter() {
+            let transaction_id = transaction.id.clone().unwrap_or_default();
+            for (output_index, output) in transaction.outputs.iter().enumerate() {
+                holdings.push(Holding {
+                    output: TransactionOutput {
+                        output_index,
+                        transaction_id: transaction_id.clone(),
+                    },
+                    owners: output.public_keys.clone(),
+                    amount: output.amount.clone(),
+                    height: *height,
+                    spent_by: None,
+                    spent_at: None,
+                });
+            }
+
+            entries.push(HistoryEntry {
+                transaction_id,
+                operation: transaction.operation.clone(),
+                height: *height,
+                spends: fulfills(transaction),
+                metadata: transaction.metadata.clone(),
+            });
+        }
+
+        for entry in entries.iter() {
+            for spent in entry.spends.iter() {
+                if let Some(holding) = holdings.iter_mut().find(|holding| holding.output == *spent)
+                {
+                    holding.spent_by = Some(entry.transaction_id.clone());
+                    holding.spent_at = entry.height;
+                }
+            }
+        }
+
+        Self {
+            asset_id: asset_id.to_string(),
+            transactions: entries,
+            holdings,
+        }
+    }
+
+    /// Outputs held at the end of the block `height`.
+    pub fn holdings_at(&self, height: u64) -> Vec<&Holding> {
+        self.holdings
+            .iter()
+            .filter(|holding| holding.is_held_at(height))
+            .collect()
+    }
+
+    /// Amount held by each public key at the end of the block `height`. An output held jointly
+    /// counts in full for each of its owners.
+    pub fn owners_at(&self, height: u64) -> Result<BTreeMap<String, u64>, Error> {
+        let mut owners = BTreeMap::new();
+        for holding in self.holdings_at(height) {
+            let amount: u64 = holding.amount.parse().unwrap_or(0);
+            for owner in holding.owners.iter() {
+                let total: &mut u64 = owners.entry(owner.clone()).or_insert(0);
+                *total = total.checked_add(amount).ok_or_else(|| {
+                    Error::InvalidAmount(format!("the shares of {owner} overflow"))
+                })?;
+            }
+        }
+        Ok(owners)
+    }
+
+    /// Outputs that led to `output`, from the CREATE to `output` itself, in the order of the
+    /// blocks. A transfer of several inputs brings the custody of each of them.
+    pub fn chain_of_custody(&self, output: &TransactionOutput) -> Vec<&Holding> {
+        let mut seen = HashSet::new();
+        let mut pending = vec![output.clone()];
+
+        while let Some(output) = pending.pop() {
+            if !seen.insert(output.clone()) {
+                continue;
+            }
+            if let Some(entry) = self
+                .transactions
+                .iter()
+                .find(|entry| entry.transaction_id == output.transaction_id)
+            {
+                pending.extend(entry.spends.iter().cloned());
+            }
+        }
+
+        // holdings are already in the order of the blocks
+        self.holdings
+            .iter()
+            .filter(|holding| seen.contains(&holding.output))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Graphviz graph of the transactions, linked by the outputs they spend. Unspent outputs
+    /// point to a box of their owners.
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n  rankdir=LR;\n", escape(&self.asset_id));
+
+        for entry in self.transactions.iter() {
+            let operation = entry
+                .operation
+                .as_ref()
+                .map_or("-", |operation| operation.as_str());
+            let height = entry
+                .height
+                .map_or(String::from("?"), |height| height.to_string());
+            dot.push_str(&format!(
+                "  \"{}\" [label=\"{operation} {}\\nheight {height}\"];\n",
+                escape(&entry.transaction_id),
+                escape(short(&entry.transaction_id)),
+            ));
+        }
+
+        for holding in self.holdings.iter() {
+            let owners: Vec<&str> = holding.owners.iter().map(|owner| short(owner)).collect();
+            let label = escape(&format!("{} to {}", holding.amount, owners.join(", ")));
+            let from = escape(&holding.output.transaction_id);
+
+            match &holding.spent_by {
+                Some(to) => dot.push_str(&format!(
+                    "  \"{from}\" -> \"{}\" [label=\"{label}\"];\n",
+                    escape(to)
+                )),
+                None => {
+                    let node = format!("{from}:{}", holding.output.output_index);
+                    dot.push_str(&format!("  \"{node}\" [shape=box, label=\"{label}\"];\n"));
+                    dot.push_str(&format!("  \"{from}\" -> \"{node}\";\n"));
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl<'a> Connection<'a> {
+    /// Fetch every transaction of `asset_id` and the height of its block, and rebuild the
+    /// ownership history of the asset.
+    pub async fn asset_history(&self, asset_id: &str) -> Result<AssetHistory, Error> {
+        let transactions = self.list_transactions(asset_id, None).await?;
+
+        let transactions = stream::iter(transactions)
+            .map(|transaction| async move {
+                let transaction_id = transaction.id.clone().unwrap_or_default();
+                let heights = self.list_blocks(&transaction_id).await?;
+                Ok::<_, Error>((transaction, heights.first().copied()))
+            })
+            .buffered(DEFAULT_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(AssetHistory::from_transactions(asset_id, transactions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        keys::SecretKey,
+        transaction::{Output, Transaction, UnspentOutput},
+    };
+
+    fn spend(
+        tx: &TransactionTemplate,
+        output_index: usize,
+        outputs: Vec<Output>,
+        key: &SecretKey,
+    ) -> TransactionTemplate {
+        let transaction = Transaction::make_transfer_transaction(
+            vec![UnspentOutput {
+                output_index,
+                tx: tx.clone(),
+            }],
+            outputs,
+            None,
+        );
+        Transaction::sign_transaction(&transaction, vec![key]).unwrap()
+    }
+
+    #[test]
+    fn test_owners_and_custody() {
+        let alice = SecretKey::generate();
+        let bob = SecretKey::generate();
+        let carol = SecretKey::generate().public_key();
+
+        // alice issues 3 shares, gives 2 to bob, who passes them on to carol
+        let create = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                Some(serde_json::json!({ "serial": "A-1" })),
+                None,
+                vec![Transaction::make_ed25519_output(&alice.public_key(), 3)],
+                vec![alice.public_key().to_string()],
+            ),
+            vec![&alice],
+        )
+        .unwrap();
+        let to_bob = spend(
+            &create,
+            0,
+            vec![
+                Transaction::make_ed25519_output(&bob.public_key(), 2),
+                Transaction::make_ed25519_output(&alice.public_key(), 1),
+            ],
+            &alice,
+        );
+        let to_carol = spend(
+            &to_bob,
+            0,
+            vec![Transaction::make_ed25519_output(&carol, 2)],
+            &bob,
+        );
+
+        let asset_id = create.id.clone().unwrap();
+        let history = AssetHistory::from_transactions(
+            &asset_id,
+            vec![
+                (to_carol.clone(), Some(3)),
+                (create.clone(), Some(1)),
+                (to_bob.clone(), Some(2)),
+            ],
+        );
+        assert_eq!(history.transactions[0].transaction_id, asset_id);
+
+        let alice = alice.public_key().to_string();
+        let bob = bob.public_key().to_string();
+        let carol = carol.to_string();
+        assert_eq!(
+            history.owners_at(1).unwrap(),
+            BTreeMap::from([(alice.clone(), 3)])
+        );
+        assert_eq!(
+            history.owners_at(2).unwrap(),
+            BTreeMap::from([(alice.clone(), 1), (bob, 2)])
+        );
+        assert_eq!(
+            history.owners_at(3).unwrap(),
+            BTreeMap::from([(alice, 1), (carol, 2)])
+        );
+
+        let custody = history.chain_of_custody(&TransactionOutput {
+            output_index: 0,
+            transaction_id: to_carol.id.clone().unwrap(),
+        });
+        let heights: Vec<_> = custody.iter().map(|holding| holding.height).collect();
+        assert_eq!(heights, vec![Some(1), Some(2), Some(3)]);
+
+        let dot = history.to_dot();
+        assert!(dot.contains(&format!(
+            "\"{asset_id}\" -> \"{}\"",
+            to_bob.id.as_deref().unwrap()
+        )));
+        assert!(dot.contains("shape=box"));
+    }
+
+    #[test]
+    fn test_dot_escaping_and_overflow() {
+        let owner = SecretKey::generate();
+        let create = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                None,
+                None,
+                vec![
+                    Transaction::make_ed25519_output(&owner.public_key(), u64::MAX),
+                    Transaction::make_ed25519_output(&owner.public_key(), 1),
+                ],
+                vec![owner.public_key().to_string()],
+            ),
+            vec![&owner],
+        )
+        .unwrap();
+
+        // ids come from the node, so quotes in them must not end the DOT strings
+        let history = AssetHistory::from_transactions(r#"a"b\c"#, vec![(create, Some(1))]);
+        assert!(history.to_dot().starts_with(r#"digraph "a\"b\\c" {"#));
+
+        assert!(matches!(history.owners_at(1), Err(Error::InvalidAmount(_))));
+    }
+}
@@ -0,0 +1,553 @@
+//! Local SQLite index of the ledger, for queries the nodes do not answer.
+//!
+//! The indexer fetches the blocks one height after the other, from the first one it is missing,
+//! and keeps the transactions, their outputs with owners and spent status, the assets and the
+//! metadata. Assets and metadata are stored as JSON and queried with SQLite JSON paths such as
+//! `$.serial` or `$.items[0].name`.
+//!
+//! Neither the blocks nor the transactions of the ledger are dated. A block is only given a time,
+//! `seen_at`, when `follow` hears of its commit from the node as it happens, which is close to
+//! the commit time. Blocks stored by catching up with `sync` have no time, as the time they
+//! were stored says nothing of when they were committed, and `transactions_between` leaves them
+//! out.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    connection::Connection,
+    error::Error,
+    transaction::{
+        Asset, AssetSearchResult, Block, MetadataSearchResult, Operation, TransactionOutput,
+        TransactionTemplate,
+    },
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS blocks (
+        height INTEGER PRIMARY KEY,
+        -- when `follow` saw the block committed, NULL for blocks stored by catching up
+        seen_at INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS transactions (
+        id TEXT PRIMARY KEY,
+        height INTEGER NOT NULL,
+        operation TEXT NOT NULL,
+        asset_id TEXT NOT NULL,
+        metadata TEXT,
+        body TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS transactions_asset_id ON transactions (asset_id);
+    CREATE INDEX IF NOT EXISTS transactions_height ON transactions (height);
+    CREATE TABLE IF NOT EXISTS assets (
+        id TEXT PRIMARY KEY,
+        data TEXT
+    );
+    CREATE TABLE IF NOT EXISTS outputs (
+        transaction_id TEXT NOT NULL,
+        output_index INTEGER NOT NULL,
+        asset_id TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        spent_by TEXT,
+        PRIMARY KEY (transaction_id, output_index)
+    );
+    CREATE INDEX IF NOT EXISTS outputs_asset_id ON outputs (asset_id);
+    CREATE TABLE IF NOT EXISTS owners (
+        transaction_id TEXT NOT NULL,
+        output_index INTEGER NOT NULL,
+        public_key TEXT NOT NULL,
+        PRIMARY KEY (transaction_id, output_index, public_key)
+    );
+    CREATE INDEX IF NOT EXISTS owners_public_key ON owners (public_key);
+";
+
+/// Output of an indexed transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexedOutput {
+    pub output: TransactionOutput,
+    pub asset_id: String,
+    pub amount: String,
+    pub spent_by: Option<String>,
+}
+
+/// Filter of `Indexer::outputs`.
+#[derive(Debug, Clone, Default)]
+pub struct OutputQuery {
+    pub public_key: Option<String>,
+    pub asset_id: Option<String>,
+    pub spent: Option<bool>,
+}
+
+impl OutputQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn public_key(mut self, public_key: &str) -> Self {
+        self.public_key = Some(public_key.to_string());
+        self
+    }
+
+    pub fn asset_id(mut self, asset_id: &str) -> Self {
+        self.asset_id = Some(asset_id.to_string());
+        self
+    }
+
+    pub fn spent(mut self, spent: bool) -> Self {
+        self.spent = Some(spent);
+        self
+    }
+}
+
+pub struct Indexer {
+    db: rusqlite::Connection,
+}
+
+fn db_error(err: rusqlite::Error) -> Error {
+    Error::IndexerError(err.to_string())
+}
+
+fn json_text(value: &JsonValue) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+fn parse_json(text: String) -> Result<JsonValue, Error> {
+    serde_json::from_str(&text).map_err(|_| Error::SerdeError)
+}
+
+impl Indexer {
+    /// Open the index at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::with_connection(rusqlite::Connection::open(path).map_err(db_error)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::with_connection(rusqlite::Connection::open_in_memory().map_err(db_error)?)
+    }
+
+    fn with_connection(db: rusqlite::Connection) -> Result<Self, Error> {
+        db.execute_batch(SCHEMA).map_err(db_error)?;
+        Ok(Self { db })
+    }
+
+    /// Height of the last block stored.
+    pub fn last_height(&self) -> Result<Option<u64>, Error> {
+        self.db
+            .query_row("SELECT MAX(height) FROM blocks", [], |row| row.get(0))
+            .map_err(db_error)
+    }
+
+    // lowest height not stored yet, so that a gap left below the last block is filled
+    fn first_missing_height(&self) -> Result<u64, Error> {
+        self.db
+            .query_row(
+                "SELECT CASE WHEN NOT EXISTS (SELECT 1 FROM blocks WHERE height = 1) THEN 1
+                 ELSE (SELECT MIN(b.height) + 1 FROM blocks b WHERE NOT EXISTS
+                       (SELECT 1 FROM blocks c WHERE c.height = b.height + 1)) END",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(db_error)
+    }
+
+    /// Store the transactions of `block` and mark the outputs they spend. A block already
+    /// stored is left as it is.
+    pub fn index_block(&mut self, block: &Block) -> Result<(), Error> {
+        let db = self.db.transaction().map_err(db_error)?;
+
+        let inserted = db
+            .execute(
+                "INSERT OR IGNORE INTO blocks (height) VALUES (?1)",
+                params![block.height],
+            )
+            .map_err(db_error)?;
+        if inserted == 0 {
+            return Ok(());
+        }
+
+        for transaction in block.transactions.iter() {
+            let transaction_id = transaction.id.clone().unwrap_or_default();
+            let asset_id = transaction.asset_id().unwrap_or_default();
+            let operation = transaction.operation.as_ref().map_or("", Operation::as_str);
+
+            db.execute(
+                "INSERT OR IGNORE INTO transactions (id, height, operation, asset_id, metadata, body)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    transaction_id,
+                    block.height,
+                    operation,
+                    asset_id,
+                    transaction.metadata.as_ref().map(json_text),
+                    transaction.serialize_transaction_into_canonical_string(),
+                ],
+            )
+            .map_err(db_error)?;
+
+            if transaction.operation == Some(Operation::CREATE) {
+                let data = match &transaction.asset {
+                    Some(Asset::Definition(definition)) => Some(json_text(&definition.data)),
+                    _ => None,
+                };
+                db.execute(
+                    "INSERT OR IGNORE INTO assets (id, data) VALUES (?1, ?2)",
+                    params![transaction_id, data],
+                )
+                .map_err(db_error)?;
+            }
+
+            for (output_index, output) in transaction.outputs.iter().enumerate() {
+                db.execute(
+                    "INSERT OR IGNORE INTO outputs (transaction_id, output_index, asset_id, amount)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![transaction_id, output_index, asset_id, output.amount],
+                )
+                .map_err(db_error)?;
+                for public_key in output.public_keys.iter() {
+                    db.execute(
+                        "INSERT OR IGNORE INTO owners (transaction_id, output_index, public_key)
+                         VALUES (?1, ?2, ?3)",
+                        params![transaction_id, output_index, public_key],
+                    )
+                    .map_err(db_error)?;
+                }
+            }
+
+            for input in transaction.inputs.iter() {
+                let Some(spent) = input.fulfills.clone().and_then(|fulfills| {
+                    serde_json::from_value::<TransactionOutput>(fulfills).ok()
+                }) else {
+                    continue;
+                };
+                db.execute(
+                    "UPDATE outputs SET spent_by = ?1 WHERE transaction_id = ?2 AND output_index = ?3",
+                    params![transaction_id, spent.transaction_id, spent.output_index],
+                )
+                .map_err(db_error)?;
+            }
+        }
+
+        db.commit().map_err(db_error)
+    }
+
+    // date the block `height` with the time its commit was heard of, unless it already is
+    fn mark_seen(&mut self, height: u64, seen_at: DateTime<Utc>) -> Result<(), Error> {
+        self.db
+            .execute(
+                "UPDATE blocks SET seen_at = ?2 WHERE height = ?1 AND seen_at IS NULL",
+                params![height, seen_at.timestamp()],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Fetch and store every block missing from the first one, until the node has no more, and
+    /// return the number of blocks stored.
+    pub async fn sync(&mut self, connection: &Connection<'_>) -> Result<u64, Error> {
+        let mut indexed = 0;
+
+        loop {
+            let height = self.first_missing_height()?;
+            let block = match connection.get_block(height).await {
+                Ok(block) => block,
+                Err(Error::ResponseError { status: 404, .. }) => return Ok(indexed),
+                Err(err) => return Err(err),
+            };
+            self.index_block(&block)?;
+            indexed += 1;
+        }
+    }
+
+    /// Sync, then sync again on every block the nodes announce, until the stream fails. The
+    /// announced blocks are dated with the time they are announced, see `transactions_between`.
+    pub async fn follow(&mut self, connection: &Connection<'_>) -> Result<(), Error> {
+        self.sync(connection).await?;
+
+        let mut events = connection.subscribe_valid_transactions();
+        while let Some(event) = events.next().await {
+            let event = event?;
+            let seen_at = Utc::now();
+            if !matches!(self.last_height()?, Some(height) if height >= event.height) {
+                self.sync(connection).await?;
+            }
+            self.mark_seen(event.height, seen_at)?;
+        }
+
+        Ok(())
+    }
+
+    /// Outputs matching `query`, in the order they were indexed.
+    pub fn outputs(&self, query: &OutputQuery) -> Result<Vec<IndexedOutput>, Error> {
+        let mut statement = self
+            .db
+            .prepare(
+                "SELECT o.transaction_id, o.output_index, o.asset_id, o.amount, o.spent_by
+                 FROM outputs o JOIN transactions t ON t.id = o.transaction_id
+                 WHERE (?1 IS NULL OR EXISTS (
+                        SELECT 1 FROM owners w
+                        WHERE w.transaction_id = o.transaction_id
+                          AND w.output_index = o.output_index
+                          AND w.public_key = ?1))
+                   AND (?2 IS NULL OR o.asset_id = ?2)
+                   AND (?3 IS NULL OR (o.spent_by IS NOT NULL) = ?3)
+                 ORDER BY t.height, o.transaction_id, o.output_index",
+            )
+            .map_err(db_error)?;
+
+        let rows = statement
+            .query_map(
+                params![query.public_key, query.asset_id, query.spent],
+                |row| {
+                    Ok(IndexedOutput {
+                        output: TransactionOutput {
+                            transaction_id: row.get(0)?,
+                            output_index: row.get(1)?,
+                        },
+                        asset_id: row.get(2)?,
+                        amount: row.get(3)?,
+                        spent_by: row.get(4)?,
+                    })
+                },
+            )
+            .map_err(db_error)?;
+
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+
+    /// Assets whose data holds `value` at the JSON path `path`.
+    pub fn find_assets(
+        &self,
+        path: &str,
+        value: &JsonValue,
+    ) -> Result<Vec<AssetSearchResult>, Error> {
+        let rows: Vec<(String, String)> = self.query_json(
+            "SELECT a.id, a.data FROM assets a JOIN transactions t ON t.id = a.id
+             WHERE json_extract(a.data, ?1) = json_extract(?2, '$')
+             ORDER BY t.height",
+            path,
+            value,
+        )?;
+
+        rows.into_iter()
+            .map(|(id, data)| {
+                Ok(AssetSearchResult {
+                    id,
+                    data: parse_json(data)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Metadata of the transactions holding `value` at the JSON path `path`.
+    pub fn find_metadata(
+        &self,
+        path: &str,
+        value: &JsonValue,
+    ) -> Result<Vec<MetadataSearchResult>, Error> {
+        let rows: Vec<(String, String)> = self.query_json(
+            "SELECT id, metadata FROM transactions
+             WHERE json_extract(metadata, ?1) = json_extract(?2, '$')
+             ORDER BY height",
+            path,
+            value,
+        )?;
+
+        rows.into_iter()
+            .map(|(id, metadata)| {
+                Ok(MetadataSearchResult {
+                    id,
+                    metadata: parse_json(metadata)?,
+                })
+            })
+            .collect()
+    }
+
+    fn query_json(
+        &self,
+        sql: &str,
+        path: &str,
+        value: &JsonValue,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let mut statement = self.db.prepare(sql).map_err(db_error)?;
+        let rows = statement
+            .query_map(params![path, json_text(value)], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(db_error)?;
+
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+
+    /// Transactions of the blocks seen committed by `follow` between `from` and `to`, both
+    /// included. Blocks stored by `sync` alone have no time and are never returned.
+    pub fn transactions_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TransactionTemplate>, Error> {
+        let mut statement = self
+            .db
+            .prepare(
+                "SELECT t.body FROM transactions t JOIN blocks b ON b.height = t.height
+                 WHERE b.seen_at BETWEEN ?1 AND ?2
+                 ORDER BY t.height",
+            )
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map(params![from.timestamp(), to.timestamp()], |row| {
+                row.get::<_, String>(0)
+            })
+            .map_err(db_error)?;
+
+        rows.map(|body| {
+            serde_json::from_str(&body.map_err(db_error)?).map_err(|_| Error::SerdeError)
+        })
+        .collect()
+    }
+
+    pub fn get_transaction(
+        &self,
+        transaction_id: &str,
+    ) -> Result<Option<TransactionTemplate>, Error> {
+        let body: Option<String> = self
+            .db
+            .query_row(
+                "SELECT body FROM transactions WHERE id = ?1",
+                params![transaction_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)?;
+
+        body.map(|body| serde_json::from_str(&body).map_err(|_| Error::SerdeError))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        keys::SecretKey,
+        transaction::{Transaction, UnspentOutput},
+    };
+
+    #[test]
+    fn test_index_and_query() {
+        let alice = SecretKey::generate();
+        let bob = SecretKey::generate().public_key();
+
+        let create = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                Some(json!({ "serial": "A-1", "specs": { "color": "red" } })),
+                Some(json!({ "plant": "Lyon" })),
+                vec![Transaction::make_ed25519_output(&alice.public_key(), 3)],
+                vec![alice.public_key().to_string()],
+            ),
+            vec![&alice],
+        )
+        .unwrap();
+        let transfer = Transaction::sign_transaction(
+            &Transaction::make_transfer_transaction(
+                vec![UnspentOutput {
+                    output_index: 0,
+                    tx: create.clone(),
+                }],
+                vec![
+                    Transaction::make_ed25519_output(&bob, 2),
+                    Transaction::make_ed25519_output(&alice.public_key(), 1),
+                ],
+                Some(json!({ "plant": "Turin" })),
+            ),
+            vec![&alice],
+        )
+        .unwrap();
+        let asset_id = create.id.clone().unwrap();
+
+        let mut indexer = Indexer::open_in_memory().unwrap();
+        let day = |day| Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap();
+        for (height, transaction) in [(1, &create), (2, &transfer)] {
+            let block = Block {
+                height,
+                transactions: vec![transaction.clone()],
+            };
+            indexer.index_block(&block).unwrap();
+            // a block stored twice is ignored
+            indexer.index_block(&block).unwrap();
+        }
+        assert_eq!(indexer.last_height().unwrap(), Some(2));
+        // only the transfer was seen committed live
+        indexer.mark_seen(2, day(3)).unwrap();
+        indexer.mark_seen(2, day(5)).unwrap();
+
+        let unspent = indexer
+            .outputs(
+                &OutputQuery::new()
+                    .public_key(&alice.public_key().to_string())
+                    .spent(false),
+            )
+            .unwrap();
+        assert_eq!(unspent.len(), 1);
+        assert_eq!(unspent[0].amount, "1");
+        let spent = indexer.outputs(&OutputQuery::new().spent(true)).unwrap();
+        assert_eq!(spent[0].spent_by, transfer.id);
+        let of_asset = indexer
+            .outputs(
+                &OutputQuery::new()
+                    .public_key(&bob.to_string())
+                    .asset_id(&asset_id),
+            )
+            .unwrap();
+        assert_eq!(of_asset.len(), 1);
+
+        let assets = indexer.find_assets("$.specs.color", &json!("red")).unwrap();
+        assert_eq!(assets[0].id, asset_id);
+        assert!(indexer
+            .find_assets("$.serial", &json!("B-2"))
+            .unwrap()
+            .is_empty());
+        let metadata = indexer.find_metadata("$.plant", &json!("Turin")).unwrap();
+        assert_eq!(metadata[0].id, transfer.id.clone().unwrap());
+
+        let transactions = indexer.transactions_between(day(2), day(4)).unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].id, transfer.id);
+        assert!(indexer
+            .transactions_between(day(1), day(2))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            indexer.get_transaction(&asset_id).unwrap().unwrap().id,
+            create.id
+        );
+    }
+
+    #[test]
+    fn test_first_missing_height() {
+        let mut indexer = Indexer::open_in_memory().unwrap();
+        assert_eq!(indexer.first_missing_height().unwrap(), 1);
+
+        let block = |height| Block {
+            height,
+            transactions: Vec::new(),
+        };
+        indexer.index_block(&block(2)).unwrap();
+        assert_eq!(indexer.first_missing_height().unwrap(), 1);
+
+        // a gap below the last block is filled before going on
+        for height in [1, 4] {
+            indexer.index_block(&block(height)).unwrap();
+        }
+        assert_eq!(indexer.first_missing_height().unwrap(), 3);
+        indexer.index_block(&block(3)).unwrap();
+        assert_eq!(indexer.first_missing_height().unwrap(), 5);
+    }
+}
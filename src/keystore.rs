@@ -0,0 +1,536 @@
+//! Password protected file of labeled keypairs.
+//!
+//! The keystore is a JSON document:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "kdf": { "name": "scrypt", "log_n": 15, "r": 8, "p": 1, "salt": "<hex, 32 bytes>" },
+//!   "cipher": "chacha20poly1305",
+//!   "check": { "nonce": "<hex, 12 bytes>", "ciphertext": "<hex>" },
+//!   "keys": [
+//!     {
+//!       "label": "alice",
+//!       "public_key": "<base58>",
+//!       "nonce": "<hex, 12 bytes>",
+//!       "ciphertext": "<hex>"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! The password is stretched once with scrypt into a 32 bytes key. Every secret key is sealed
+//! with ChaCha20-Poly1305 under its own random nonce, with the label, a zero byte and the public
+//! key as associated data, so entries cannot be swapped or relabeled. `check` seals an empty
+//! message, which tells a wrong password apart from a corrupted entry. Labels and public keys
+//! are stored in clear so they can be listed without the password.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    error::Error,
+    keys::{KeyFormat, PublicKey, SecretKey},
+    randombytes,
+    transaction::{Transaction, TransactionTemplate},
+    Ed25519Keypair,
+};
+
+const VERSION: u32 = 1;
+const KDF: &str = "scrypt";
+const CIPHER: &str = "chacha20poly1305";
+const NONCE_LENGTH: usize = 12;
+const CHECK_AAD: &[u8] = b"bigchaindb keystore";
+
+/// Cost of the scrypt key derivation, see `scrypt::Params`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for KdfParams {
+    // about 32 MiB of memory, as recommended for interactive logins
+    fn default() -> Self {
+        Self {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Kdf {
+    name: String,
+    #[serde(flatten)]
+    params: KdfParams,
+    #[serde(with = "hex")]
+    salt: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Sealed {
+    #[serde(with = "hex")]
+    nonce: Vec<u8>,
+    #[serde(with = "hex")]
+    ciphertext: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    label: String,
+    public_key: PublicKey,
+    #[serde(flatten)]
+    sealed: Sealed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    kdf: Kdf,
+    cipher: String,
+    check: Sealed,
+    keys: Vec<Entry>,
+}
+
+impl KeystoreFile {
+    fn read(path: &Path) -> Result<Self, Error> {
+        let json = fs::read(path)
+            .map_err(|err| Error::KeystoreError(format!("{}: {err}", path.display())))?;
+        let file: Self = serde_json::from_slice(&json)
+            .map_err(|err| Error::KeystoreError(format!("{}: {err}", path.display())))?;
+
+        if file.version != VERSION || file.kdf.name != KDF || file.cipher != CIPHER {
+            return Err(Error::KeystoreError(format!(
+                "unsupported keystore version {} ({}, {})",
+                file.version, file.kdf.name, file.cipher
+            )));
+        }
+        Ok(file)
+    }
+}
+
+/// Labeled keypairs kept encrypted on disk, unlocked with a password.
+///
+/// Changes are written to the file as soon as they are made.
+pub struct Keystore {
+    path: PathBuf,
+    file: KeystoreFile,
+    cipher: ChaCha20Poly1305,
+}
+
+/// Label and public key of a stored keypair.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeystoreEntry {
+    pub label: String,
+    pub public_key: PublicKey,
+}
+
+fn derive_cipher(password: &str, kdf: &Kdf) -> Result<ChaCha20Poly1305, Error> {
+    let params = scrypt::Params::new(kdf.params.log_n, kdf.params.r, kdf.params.p, 32)
+        .map_err(|err| Error::KeystoreError(format!("invalid scrypt parameters: {err}")))?;
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(password.as_bytes(), &kdf.salt, &params, &mut key[..])
+        .map_err(|err| Error::KeystoreError(err.to_string()))?;
+
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key[..])))
+}
+
+fn seal(cipher: &ChaCha20Poly1305, msg: &[u8], aad: &[u8]) -> Result<Sealed, Error> {
+    let nonce: [u8; NONCE_LENGTH] = rand::random();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg, aad })
+        .map_err(|_| Error::KeystoreError(String::from("encryption failed")))?;
+
+    Ok(Sealed {
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+}
+
+fn open(cipher: &ChaCha20Poly1305, sealed: &Sealed, aad: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+    if sealed.nonce.len() != NONCE_LENGTH {
+        return None;
+    }
+    cipher
+        .decrypt(
+            Nonce::from_slice(&sealed.nonce),
+            Payload {
+                msg: &sealed.ciphertext,
+                aad,
+            },
+        )
+        .ok()
+        .map(Zeroizing::new)
+}
+
+// binds a sealed secret key to its label and public key
+fn entry_aad(label: &str, public_key: &PublicKey) -> Vec<u8> {
+    let mut aad = label.as_bytes().to_vec();
+    aad.push(0);
+    aad.extend_from_slice(public_key.as_bytes());
+    aad
+}
+
+impl Keystore {
+    /// Create an empty keystore at `path`, which must not exist yet.
+    pub fn create(path: impl AsRef<Path>, password: &str) -> Result<Self, Error> {
+        Self::create_with_kdf(path, password, KdfParams::default())
+    }
+
+    pub fn create_with_kdf(
+        path: impl AsRef<Path>,
+        password: &str,
+        params: KdfParams,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            return Err(Error::KeystoreError(format!(
+                "{} already exists",
+                path.display()
+            )));
+        }
+
+        let mut salt = [0u8; 32];
+        randombytes(&mut salt);
+        let kdf = Kdf {
+            name: KDF.to_string(),
+            params,
+            salt: salt.to_vec(),
+        };
+        let cipher = derive_cipher(password, &kdf)?;
+
+        let keystore = Self {
+            file: KeystoreFile {
+                version: VERSION,
+                kdf,
+                cipher: CIPHER.to_string(),
+                check: seal(&cipher, &[], CHECK_AAD)?,
+                keys: Vec::new(),
+            },
+            path,
+            cipher,
+        };
+        keystore.save()?;
+
+        Ok(keystore)
+    }
+
+    /// Open the keystore at `path`, failing with `Error::InvalidPassword` if `password` is not
+    /// the one it was created with.
+    pub fn open(path: impl AsRef<Path>, password: &str) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let file = KeystoreFile::read(&path)?;
+        let cipher = derive_cipher(password, &file.kdf)?;
+        open(&cipher, &file.check, CHECK_AAD).ok_or(Error::InvalidPassword)?;
+
+        Ok(Self { path, file, cipher })
+    }
+
+    /// Labels and public keys stored at `path`, read without the password.
+    pub fn list_file(path: impl AsRef<Path>) -> Result<Vec<KeystoreEntry>, Error> {
+        let file = KeystoreFile::read(path.as_ref())?;
+        Ok(file.keys.iter().map(Self::entry).collect())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn list(&self) -> Vec<KeystoreEntry> {
+        self.file.keys.iter().map(Self::entry).collect()
+    }
+
+    pub fn contains(&self, label: &str) -> bool {
+        self.file.keys.iter().any(|entry| entry.label == label)
+    }
+
+    /// Store `secret_key` under `label` and return its public key.
+    pub fn insert(&mut self, label: &str, secret_key: &SecretKey) -> Result<PublicKey, Error> {
+        if label.is_empty() {
+            return Err(Error::KeystoreError(String::from("empty label")));
+        }
+        if self.contains(label) {
+            return Err(Error::KeystoreError(format!(
+                "label {label} already exists"
+            )));
+        }
+
+        let public_key = secret_key.public_key();
+        let sealed = seal(
+            &self.cipher,
+            secret_key.as_bytes(),
+            &entry_aad(label, &public_key),
+        )?;
+        self.file.keys.push(Entry {
+            label: label.to_string(),
+            public_key,
+            sealed,
+        });
+
+        if let Err(err) = self.save() {
+            self.file.keys.pop();
+            return Err(err);
+        }
+        Ok(public_key)
+    }
+
+    /// Store a new random keypair under `label`.
+    pub fn generate(&mut self, label: &str) -> Result<PublicKey, Error> {
+        self.insert(label, &SecretKey::generate())
+    }
+
+    /// Store the secret key `encoded` in `format` under `label`.
+    pub fn import(
+        &mut self,
+        label: &str,
+        encoded: &str,
+        format: KeyFormat,
+    ) -> Result<PublicKey, Error> {
+        self.insert(label, &SecretKey::decode(encoded, format)?)
+    }
+
+    /// Secret key of `label` encoded in `format`.
+    pub fn export(&self, label: &str, format: KeyFormat) -> Result<Zeroizing<String>, Error> {
+        Ok(self.secret_key(label)?.encode(format))
+    }
+
+    pub fn remove(&mut self, label: &str) -> Result<(), Error> {
+        let index = self.index(label)?;
+        let entry = self.file.keys.remove(index);
+
+        if let Err(err) = self.save() {
+            self.file.keys.insert(index, entry);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    pub fn public_key(&self, label: &str) -> Result<PublicKey, Error> {
+        Ok(self.file.keys[self.index(label)?].public_key)
+    }
+
+    pub fn secret_key(&self, label: &str) -> Result<SecretKey, Error> {
+        let entry = &self.file.keys[self.index(label)?];
+        let corrupted = || Error::KeystoreError(format!("corrupted key {label}"));
+
+        let bytes = open(
+            &self.cipher,
+            &entry.sealed,
+            &entry_aad(&entry.label, &entry.public_key),
+        )
+        .ok_or_else(corrupted)?;
+        let secret_key = SecretKey::from_bytes(&bytes)?;
+        if secret_key.public_key() != entry.public_key {
+            return Err(corrupted());
+        }
+
+        Ok(secret_key)
+    }
+
+    pub fn keypair(&self, label: &str) -> Result<Ed25519Keypair, Error> {
+        Ok(Ed25519Keypair::from_secret_key(self.secret_key(label)?))
+    }
+
+    /// Sign `transaction` with the keys of `labels`, see `Transaction::sign_transaction`.
+    pub fn sign_transaction(
+        &self,
+        transaction: &TransactionTemplate,
+        labels: &[&str],
+    ) -> Result<TransactionTemplate, Error> {
+        let secret_keys = labels
+            .iter()
+            .map(|label| self.secret_key(label))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Transaction::sign_transaction(transaction, secret_keys.iter().collect())
+    }
+
+    fn index(&self, label: &str) -> Result<usize, Error> {
+        self.file
+            .keys
+            .iter()
+            .position(|entry| entry.label == label)
+            .ok_or_else(|| Error::KeyNotFound(label.to_string()))
+    }
+
+    fn entry(entry: &Entry) -> KeystoreEntry {
+        KeystoreEntry {
+            label: entry.label.clone(),
+            public_key: entry.public_key,
+        }
+    }
+
+    // written to a temporary file then renamed, so a crash never leaves a truncated keystore
+    fn save(&self) -> Result<(), Error> {
+        let json = serde_json::to_vec_pretty(&self.file).map_err(|_| Error::SerdeError)?;
+        let tmp = self
+            .path
+            .with_extension(format!("{:x}.tmp", rand::random::<u32>()));
+
+        let written = write_private(&tmp, &json).and_then(|()| fs::rename(&tmp, &self.path));
+        if let Err(err) = written {
+            let _ = fs::remove_file(&tmp);
+            return Err(Error::KeystoreError(format!(
+                "{}: {err}",
+                self.path.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+// readable by the owner only
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_KEY: &str = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX";
+    const PRIVATE_KEY: &str = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT";
+
+    // cheap parameters, the default ones take a while in debug builds
+    const TEST_KDF: KdfParams = KdfParams {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+
+    fn keystore_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "bigchaindb-keystore-{}.json",
+            rand::random::<u64>()
+        ))
+    }
+
+    #[test]
+    fn test_keystore() {
+        let path = keystore_path();
+        let mut keystore = Keystore::create_with_kdf(&path, "password", TEST_KDF).unwrap();
+
+        let public_key = keystore
+            .import("alice", PRIVATE_KEY, KeyFormat::Base58)
+            .unwrap();
+        assert_eq!(public_key.to_string(), PUBLIC_KEY);
+        let bob = keystore.generate("bob").unwrap();
+        assert!(keystore.generate("bob").is_err());
+
+        // the file holds no secret key in clear
+        let json = fs::read_to_string(&path).unwrap();
+        assert!(json.contains(PUBLIC_KEY));
+        assert!(!json.contains(PRIVATE_KEY));
+
+        let mut keystore = Keystore::open(&path, "password").unwrap();
+        let labels: Vec<_> = keystore
+            .list()
+            .into_iter()
+            .map(|entry| entry.label)
+            .collect();
+        assert_eq!(labels, vec!["alice", "bob"]);
+        assert_eq!(keystore.keypair("bob").unwrap().pk, bob);
+        assert_eq!(
+            *keystore.export("alice", KeyFormat::Base58).unwrap(),
+            PRIVATE_KEY
+        );
+        assert_eq!(
+            SecretKey::from_pkcs8_pem(&keystore.export("alice", KeyFormat::Pkcs8Pem).unwrap())
+                .unwrap()
+                .to_string(),
+            PRIVATE_KEY
+        );
+        assert!(matches!(
+            keystore.secret_key("carol"),
+            Err(Error::KeyNotFound(_))
+        ));
+
+        assert!(matches!(
+            Keystore::open(&path, "wrong"),
+            Err(Error::InvalidPassword)
+        ));
+        assert_eq!(Keystore::list_file(&path).unwrap().len(), 2);
+
+        keystore.remove("bob").unwrap();
+        assert_eq!(Keystore::list_file(&path).unwrap().len(), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_swapped_entries_are_rejected() {
+        let path = keystore_path();
+        let mut keystore = Keystore::create_with_kdf(&path, "password", TEST_KDF).unwrap();
+        keystore.generate("alice").unwrap();
+        keystore.generate("bob").unwrap();
+
+        let mut file = KeystoreFile::read(&path).unwrap();
+        let alice = file.keys[0].sealed.clone();
+        file.keys[0].sealed = file.keys[1].sealed.clone();
+        file.keys[1].sealed = alice;
+        fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+
+        let keystore = Keystore::open(&path, "password").unwrap();
+        assert!(matches!(
+            keystore.secret_key("alice"),
+            Err(Error::KeystoreError(_))
+        ));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sign_transaction_by_label() {
+        let path = keystore_path();
+        let mut keystore = Keystore::create_with_kdf(&path, "password", TEST_KDF).unwrap();
+        keystore
+            .import("alice", PRIVATE_KEY, KeyFormat::Base58)
+            .unwrap();
+
+        let condition =
+            Transaction::make_ed25519_condition(&PUBLIC_KEY.parse().unwrap(), true).unwrap();
+        let output = Transaction::make_output(condition, String::from("1"));
+        let transaction = Transaction::make_create_transaction(
+            None,
+            None,
+            vec![output],
+            vec![PUBLIC_KEY.to_string()],
+        );
+
+        let signed = keystore.sign_transaction(&transaction, &["alice"]).unwrap();
+        let expected =
+            Transaction::sign_transaction(&transaction, vec![&PRIVATE_KEY.parse().unwrap()])
+                .unwrap();
+        assert_eq!(signed.id, expected.id);
+        assert!(keystore.sign_transaction(&transaction, &["bob"]).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
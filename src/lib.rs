@@ -1,9 +1,31 @@
+pub mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+pub mod client;
 pub mod connection;
+pub mod derivation;
+pub mod envelope;
 pub mod error;
+pub mod health;
+pub mod history;
+#[cfg(feature = "indexer")]
+pub mod indexer;
+pub mod keys;
+pub mod keystore;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod pagination;
+pub mod rate_limit;
 pub mod request;
+pub mod retry;
+pub mod selection;
 pub mod sha256_hash;
+pub mod stream;
+pub mod telemetry;
 pub mod transaction;
 pub mod transport;
+pub mod wallet;
 
 pub mod json {
     pub use serde_json::*;
@@ -11,6 +33,9 @@ pub mod json {
 
 
 use crypto_conditions::{fulfillment::Fulfillment, Ed25519Sha256};
+use derivation::DerivationPath;
+use error::Error;
+use keys::{PublicKey, SecretKey};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use stable_sort::stable_sorted;
@@ -21,30 +46,54 @@ pub fn randombytes(seed: &mut [u8; 32]) {
     rng.fill_bytes(seed);
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Ed25519Keypair {
-    pub pk: String,
-    pub sk: String,
+    pub pk: PublicKey,
+    pub sk: SecretKey,
 }
 
-pub fn ed25519_keypair() -> Ed25519Keypair {
-    let mut pk: [u8; 32] = [0; 32];
-    let mut sk: [u8; 64] = [0; 64];
-    let mut seed: [u8; 32] = [0; 32];
+impl Ed25519Keypair {
+    pub fn from_secret_key(sk: SecretKey) -> Self {
+        Self {
+            pk: sk.public_key(),
+            sk,
+        }
+    }
 
-    randombytes(&mut seed);
-    tweetnacl::sign_keypair_seed(&mut pk, &mut sk, &seed);
+    /// Keypair whose secret key is the 32 bytes `seed`, so the same seed always gives the
+    /// same keypair.
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        Ok(Self::from_secret_key(SecretKey::from_bytes(seed)?))
+    }
 
-    let sk = &sk[0..32];
-    let pk = bs58::encode(pk).into_string();
-    let sk = bs58::encode(sk).into_string();
-    Ed25519Keypair { pk, sk }
+    /// Keypair derived along `path` from the BIP39 mnemonic `phrase`, see `derivation`.
+    pub fn from_mnemonic(
+        phrase: &str,
+        passphrase: &str,
+        path: &DerivationPath,
+    ) -> Result<Self, Error> {
+        let seed = derivation::mnemonic_to_seed(phrase, passphrase)?;
+        Ok(Self::from_secret_key(derivation::derive_secret_key(
+            &seed[..],
+            path,
+        )))
+    }
+}
+
+pub fn ed25519_keypair() -> Ed25519Keypair {
+    Ed25519Keypair::from_secret_key(SecretKey::generate())
 }
 
 #[stable_sorted]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Details {
+    // empty for a threshold condition
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub public_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subconditions: Option<Vec<Details>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<usize>,
     #[serde(rename = "type")]
     pub type_: String,
 }
@@ -63,6 +112,8 @@ pub fn cc_jsonify(fulfillment: Ed25519Sha256) -> Option<JsonBody> {
         let details = Details {
             type_: String::from(Ed25519Sha256::TYPE_NAME),
             public_key: bs58::encode(fulfillment.public_key.unwrap_or_default()).into_string(),
+            subconditions: None,
+            threshold: None,
         };
 
         return Some(JsonBody {
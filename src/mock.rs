@@ -0,0 +1,551 @@
+//! In-process BigchainDB node serving the v1 HTTP API from an in-memory ledger.
+//!
+//! Every committed transaction is stored in its own block, so block heights start at 1 and
+//! follow the order in which transactions were posted. Fulfillments are not verified: the mock
+//! only checks that transactions are unique and that transfers spend existing, unspent outputs.
+//! Outputs and transactions are paged with `limit` and `offset` when they are given.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use hyper::{
+    server::conn::AddrIncoming,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde_json::{json, Value as JsonValue};
+use tokio::sync::oneshot;
+
+use crate::{
+    error::Error,
+    transaction::{Operation, TransactionOutput, TransactionTemplate},
+};
+
+/// Failure applied by the mock node to the next request it receives.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// Wait for the given duration before answering the request.
+    Timeout(Duration),
+    /// Answer the request with `500 Internal Server Error`.
+    InternalServerError,
+    /// Close the connection without answering.
+    DropConnection,
+    /// Answer the request with `429 Too Many Requests` and a `Retry-After` of the given
+    /// number of seconds.
+    TooManyRequests(u64),
+    /// Answer the request with every output or transaction, ignoring `limit` and `offset`.
+    IgnorePaging,
+}
+
+#[derive(Debug, Default)]
+struct Ledger {
+    transactions: Vec<TransactionTemplate>,
+    heights: HashMap<String, usize>,
+    // (transaction_id, output_index) -> id of the spending transaction
+    spent: HashMap<(String, usize), String>,
+    faults: VecDeque<Fault>,
+}
+
+impl Ledger {
+    fn get(&self, transaction_id: &str) -> Option<&TransactionTemplate> {
+        self.heights
+            .get(transaction_id)
+            .map(|height| &self.transactions[height - 1])
+    }
+
+    fn commit(&mut self, transaction: TransactionTemplate) -> Result<(), String> {
+        let Some(transaction_id) = transaction.id.clone() else {
+            return Err(String::from(
+                "Invalid transaction (ValidationError): missing id",
+            ));
+        };
+
+        if self.heights.contains_key(&transaction_id) {
+            return Err(format!(
+                "Invalid transaction (DuplicateTransaction): transaction `{transaction_id}` already exists"
+            ));
+        }
+
+        let mut spends = Vec::new();
+        if transaction.operation == Some(Operation::TRANSFER) {
+            for input in transaction.inputs.iter() {
+                let link: TransactionOutput = input
+                    .fulfills
+                    .clone()
+                    .and_then(|fulfills| serde_json::from_value(fulfills).ok())
+                    .ok_or_else(|| {
+                        String::from("Invalid transaction (ValidationError): invalid fulfills")
+                    })?;
+
+                let exists = self
+                    .get(&link.transaction_id)
+                    .map(|tx| link.output_index < tx.outputs.len())
+                    .unwrap_or(false);
+                if !exists {
+                    return Err(format!(
+                        "Invalid transaction (InputDoesNotExist): input `{}` doesn't exist",
+                        link.transaction_id
+                    ));
+                }
+
+                let key = (link.transaction_id, link.output_index);
+                if self.spent.contains_key(&key) || spends.contains(&key) {
+                    return Err(format!(
+                        "Invalid transaction (DoubleSpend): input `{}` was already spent",
+                        key.0
+                    ));
+                }
+                spends.push(key);
+            }
+        }
+
+        for key in spends {
+            self.spent.insert(key, transaction_id.clone());
+        }
+        self.transactions.push(transaction);
+        self.heights.insert(transaction_id, self.transactions.len());
+
+        Ok(())
+    }
+
+    fn outputs(&self, public_key: &str, spent: Option<bool>) -> Vec<TransactionOutput> {
+        let mut outputs = Vec::new();
+
+        for tx in self.transactions.iter() {
+            let transaction_id = tx.id.clone().unwrap_or_default();
+            for (output_index, output) in tx.outputs.iter().enumerate() {
+                if !output.public_keys.iter().any(|key| key == public_key) {
+                    continue;
+                }
+
+                let is_spent = self
+                    .spent
+                    .contains_key(&(transaction_id.clone(), output_index));
+                if spent.map(|spent| spent == is_spent).unwrap_or(true) {
+                    outputs.push(TransactionOutput {
+                        output_index,
+                        transaction_id: transaction_id.clone(),
+                    });
+                }
+            }
+        }
+
+        outputs
+    }
+
+    // (id, document) of the assets, or of the metadata, whose strings contain `search`
+    fn search(&self, documents: &str, search: &str, limit: Option<usize>) -> Vec<JsonValue> {
+        let search = search.to_lowercase();
+
+        self.transactions
+            .iter()
+            .filter_map(|tx| {
+                let id = tx.id.clone()?;
+                match documents {
+                    "assets" => {
+                        let data = tx.asset.as_ref()?.get_definition_data()?;
+                        contains_text(data, &search).then(|| json!({ "id": id, "data": data }))
+                    }
+                    _ => {
+                        let metadata = tx.metadata.as_ref()?;
+                        contains_text(metadata, &search)
+                            .then(|| json!({ "id": id, "metadata": metadata }))
+                    }
+                }
+            })
+            .take(limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    fn asset_id(tx: &TransactionTemplate) -> Option<String> {
+        match tx.operation {
+            Some(Operation::CREATE) => tx.id.clone(),
+            _ => tx.asset.as_ref().and_then(|asset| asset.get_link_id()),
+        }
+    }
+}
+
+// the text search of the node matches the string values of a document, not its keys
+fn contains_text(value: &JsonValue, search: &str) -> bool {
+    match value {
+        JsonValue::String(text) => text.to_lowercase().contains(search),
+        JsonValue::Array(values) => values.iter().any(|value| contains_text(value, search)),
+        JsonValue::Object(fields) => fields.values().any(|value| contains_text(value, search)),
+        _ => false,
+    }
+}
+
+/// Mock BigchainDB node listening on a local port until it is dropped.
+#[derive(Debug)]
+pub struct MockNode {
+    addr: SocketAddr,
+    ledger: Arc<Mutex<Ledger>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockNode {
+    /// Start a node on a random local port.
+    pub async fn start() -> Result<Self, Error> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let incoming = AddrIncoming::bind(&addr).map_err(|_| Error::InternalError)?;
+        let addr = incoming.local_addr();
+
+        let ledger = Arc::new(Mutex::new(Ledger::default()));
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+
+        let service_ledger = ledger.clone();
+        let make_service = make_service_fn(move |_| {
+            let ledger = service_ledger.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| handle(ledger.clone(), request)))
+            }
+        });
+
+        let server = Server::builder(incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                shutdown_signal.await.ok();
+            });
+        tokio::spawn(server);
+
+        Ok(Self {
+            addr,
+            ledger,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// API endpoint of the node, to be passed to `Connection::new`.
+    pub fn endpoint(&self) -> String {
+        format!("http://{}/api/v1/", self.addr)
+    }
+
+    /// Queue a fault for the next request. Faults are applied in the order they were injected.
+    pub fn inject_fault(&self, fault: Fault) {
+        self.ledger.lock().unwrap().faults.push_back(fault);
+    }
+
+    /// Add a transaction to the ledger without going through the HTTP API.
+    pub fn commit(&self, transaction: TransactionTemplate) -> Result<(), String> {
+        self.ledger.lock().unwrap().commit(transaction)
+    }
+
+    /// Transactions committed so far, in block order.
+    pub fn transactions(&self) -> Vec<TransactionTemplate> {
+        self.ledger.lock().unwrap().transactions.clone()
+    }
+}
+
+impl Drop for MockNode {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+fn json_response(status: StatusCode, body: JsonValue) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    json_response(
+        status,
+        json!({ "message": message, "status": status.as_u16() }),
+    )
+}
+
+// the items from `offset` on, at most `limit` of them
+fn page<T>(items: Vec<T>, query: &HashMap<String, String>, paging: bool) -> Vec<T> {
+    if !paging {
+        return items;
+    }
+
+    let param = |name: &str| query.get(name).and_then(|value| value.parse().ok());
+    items
+        .into_iter()
+        .skip(param("offset").unwrap_or(0))
+        .take(param("limit").unwrap_or(usize::MAX))
+        .collect()
+}
+
+async fn handle(
+    ledger: Arc<Mutex<Ledger>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, io::Error> {
+    let fault = ledger.lock().unwrap().faults.pop_front();
+    let mut paging = true;
+    match fault {
+        Some(Fault::Timeout(duration)) => tokio::time::sleep(duration).await,
+        Some(Fault::InternalServerError) => {
+            return Ok(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Server Error",
+            ))
+        }
+        Some(Fault::TooManyRequests(retry_after)) => {
+            let mut response = error_response(StatusCode::TOO_MANY_REQUESTS, "Too Many Requests");
+            response
+                .headers_mut()
+                .insert("Retry-After", retry_after.to_string().parse().unwrap());
+            return Ok(response);
+        }
+        Some(Fault::DropConnection) => {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "injected fault",
+            ))
+        }
+        Some(Fault::IgnorePaging) => paging = false,
+        None => {}
+    }
+
+    let method = request.method().clone();
+    let path = request
+        .uri()
+        .path()
+        .strip_prefix("/api/v1")
+        .unwrap_or(request.uri().path())
+        .trim_matches('/')
+        .to_string();
+    let query: HashMap<String, String> = request
+        .uri()
+        .query()
+        .map(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if method == Method::POST && path == "transactions" {
+        let body = hyper::body::to_bytes(request.into_body())
+            .await
+            .map_err(io::Error::other)?;
+        let Ok(transaction) = serde_json::from_slice::<TransactionTemplate>(&body) else {
+            return Ok(error_response(
+                StatusCode::BAD_REQUEST,
+                "Invalid transaction schema",
+            ));
+        };
+
+        let response = match ledger.lock().unwrap().commit(transaction.clone()) {
+            Ok(()) => json_response(StatusCode::ACCEPTED, json!(transaction)),
+            Err(message) => error_response(StatusCode::BAD_REQUEST, &message),
+        };
+        return Ok(response);
+    }
+
+    if method != Method::GET {
+        return Ok(error_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Method Not Allowed",
+        ));
+    }
+
+    let ledger = ledger.lock().unwrap();
+    let segments: Vec<&str> = path.split('/').collect();
+    let response = match segments.as_slice() {
+        [""] => json_response(
+            StatusCode::OK,
+            json!({
+                "docs": "https://docs.bigchaindb.com/projects/server/en/v2.2.2/http-client-server-api.html",
+                "streams": "",
+                "version": "2.2.2",
+            }),
+        ),
+        ["transactions"] => {
+            let Some(asset_id) = query.get("asset_id") else {
+                return Ok(error_response(
+                    StatusCode::BAD_REQUEST,
+                    "asset_id is required",
+                ));
+            };
+            let operation = query.get("operation");
+            let transactions: Vec<&TransactionTemplate> = ledger
+                .transactions
+                .iter()
+                .filter(|tx| Ledger::asset_id(tx).as_ref() == Some(asset_id))
+                .filter(|tx| {
+                    operation
+                        .map(|operation| json!(tx.operation).as_str() == Some(operation.as_str()))
+                        .unwrap_or(true)
+                })
+                .collect();
+            json_response(StatusCode::OK, json!(page(transactions, &query, paging)))
+        }
+        ["transactions", transaction_id] => match ledger.get(transaction_id) {
+            Some(tx) => json_response(StatusCode::OK, json!(tx)),
+            None => error_response(StatusCode::NOT_FOUND, "Not found"),
+        },
+        ["outputs"] => {
+            let Some(public_key) = query.get("public_key") else {
+                return Ok(error_response(
+                    StatusCode::BAD_REQUEST,
+                    "public_key is required",
+                ));
+            };
+            let spent = query.get("spent").map(|spent| spent == "true");
+            let outputs = ledger.outputs(public_key, spent);
+            json_response(StatusCode::OK, json!(page(outputs, &query, paging)))
+        }
+        [documents @ ("assets" | "metadata")] => {
+            let Some(search) = query.get("search") else {
+                return Ok(error_response(
+                    StatusCode::BAD_REQUEST,
+                    "search is required",
+                ));
+            };
+            let limit = query.get("limit").and_then(|limit| limit.parse().ok());
+            json_response(
+                StatusCode::OK,
+                json!(ledger.search(documents, search, limit)),
+            )
+        }
+        ["blocks"] => {
+            let heights: Vec<usize> = query
+                .get("transaction_id")
+                .and_then(|transaction_id| ledger.heights.get(transaction_id))
+                .into_iter()
+                .copied()
+                .collect();
+            json_response(StatusCode::OK, json!(heights))
+        }
+        ["blocks", height] => match height.parse::<usize>() {
+            Ok(height) if (1..=ledger.transactions.len()).contains(&height) => json_response(
+                StatusCode::OK,
+                json!({
+                    "height": height,
+                    "transactions": [ledger.transactions[height - 1]],
+                }),
+            ),
+            _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+        },
+        _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    Ok(response)
+}
+
+/// Keys and transactions shared by the tests that run against a `MockNode`.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::transaction::{Output, Transaction, TransactionTemplate, UnspentOutput};
+
+    // owner of every output built here
+    pub const PUBLIC_KEY: &str = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX";
+    pub const PRIVATE_KEY: &str = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT";
+
+    /// Output of `amount` owned by `PUBLIC_KEY`.
+    pub fn output(amount: u64) -> Output {
+        Transaction::make_ed25519_output(&PUBLIC_KEY.parse().unwrap(), amount)
+    }
+
+    /// `transaction` signed with `PRIVATE_KEY`.
+    pub fn sign(transaction: &TransactionTemplate) -> TransactionTemplate {
+        Transaction::sign_transaction(transaction, vec![&PRIVATE_KEY.parse().unwrap()]).unwrap()
+    }
+
+    /// Signed CREATE of the asset `{"n": n}` with a single output of 1.
+    pub fn create_transaction(n: u64) -> TransactionTemplate {
+        sign(&Transaction::make_create_transaction(
+            Some(json!({ "n": n })),
+            None,
+            vec![output(1)],
+            vec![PUBLIC_KEY.to_string()],
+        ))
+    }
+
+    /// Signed TRANSFER of the first output of `tx` back to `PUBLIC_KEY`.
+    pub fn transfer_transaction(
+        tx: &TransactionTemplate,
+        metadata: Option<JsonValue>,
+    ) -> TransactionTemplate {
+        let unspent_output = UnspentOutput {
+            tx: tx.clone(),
+            output_index: 0,
+        };
+        sign(&Transaction::make_transfer_transaction(
+            vec![unspent_output],
+            vec![output(1)],
+            metadata,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fixtures::*, *};
+    use crate::{connection::Connection, retry::RetryPolicy};
+
+    #[tokio::test]
+    async fn test_post_and_get_transaction() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        let tx = create_transaction(0);
+        conn.post_transaction_commit(tx.clone()).await.unwrap();
+
+        let fetched = conn.get_transaction(tx.id.as_ref().unwrap()).await.unwrap();
+        assert_eq!(fetched.id, tx.id);
+        assert!(conn.get_transaction("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_outputs_and_reject_double_spend() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        let create = create_transaction(0);
+        let transfer = transfer_transaction(&create, Some(json!({"n": 1})));
+        let double_spend = transfer_transaction(&create, Some(json!({"n": 2})));
+        node.commit(create.clone()).unwrap();
+        conn.post_transaction_commit(transfer.clone())
+            .await
+            .unwrap();
+
+        assert!(conn.post_transaction_commit(double_spend).await.is_err());
+
+        let unspent = conn.list_outputs(PUBLIC_KEY, Some(false)).await.unwrap();
+        assert_eq!(unspent.len(), 1);
+        assert_eq!(unspent[0].transaction_id, transfer.id.unwrap());
+
+        let spent = conn.list_outputs(PUBLIC_KEY, Some(true)).await.unwrap();
+        assert_eq!(spent.len(), 1);
+        assert_eq!(spent[0].transaction_id, create.id.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_injected_faults() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn =
+            Connection::new(vec![endpoint.as_str()]).with_retry_policy(RetryPolicy::no_retry());
+
+        node.inject_fault(Fault::InternalServerError);
+        assert!(conn.list_outputs(PUBLIC_KEY, None).await.is_err());
+
+        node.inject_fault(Fault::DropConnection);
+        assert!(conn.list_outputs(PUBLIC_KEY, None).await.is_err());
+
+        assert!(conn
+            .list_outputs(PUBLIC_KEY, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+//! Lazily fetched streams over outputs and transactions.
+//!
+//! BigchainDB answers `outputs` and `transactions` in a single response. When `page_size` is
+//! set, pages are requested with `limit` and `offset` query parameters for servers and gateways
+//! that support them. A page larger than `page_size` means the server ignored them, in which
+//! case it is yielded as a whole and no further page is requested.
+//!
+//! Items of a page that were already on the previous one, pushed across the page boundary by a
+//! commit between the two requests, are skipped. A page holding nothing else means the server
+//! ignored `offset`, and paging stops there.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    connection::{Connection, OUTPUTS, TRANSACTIONS},
+    error::Error,
+    request::{RequestMethod, RequestOption},
+    transaction::{Operation, TransactionOutput, TransactionTemplate, UnspentOutput},
+};
+
+pub(crate) const DEFAULT_CONCURRENCY: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageOptions {
+    // number of items requested per page, `None` to fetch everything in one request
+    pub page_size: Option<usize>,
+    // maximum number of transactions fetched at the same time when resolving outputs
+    pub concurrency: usize,
+}
+
+impl Default for PageOptions {
+    fn default() -> Self {
+        Self {
+            page_size: None,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl PageOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = Some(page_size.max(1));
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+}
+
+fn paginate<'a, T, K>(
+    connection: Connection<'a>,
+    path: &'static str,
+    query: Vec<(&'static str, String)>,
+    page_size: Option<usize>,
+    // identifies an item when it shows up again on the next page
+    id: fn(&T) -> K,
+) -> BoxStream<'a, Result<T, Error>>
+where
+    T: DeserializeOwned + Send + 'a,
+    K: Eq + Hash + Send + 'a,
+{
+    stream::unfold(Some((connection, 0, HashSet::new())), move |state| {
+        let query = query.clone();
+        async move {
+            let (connection, offset, previous_page) = state?;
+
+            let limit = page_size.map(|page_size| page_size.to_string());
+            let offset_value = offset.to_string();
+            let mut params: HashMap<&str, &str> = query
+                .iter()
+                .map(|(key, value)| (*key, value.as_str()))
+                .collect();
+            if let Some(limit) = &limit {
+                params.insert("limit", limit);
+                params.insert("offset", &offset_value);
+            }
+            let options = RequestOption::new()
+                .method(RequestMethod::Get)
+                .query(params);
+
+            let page = match connection.request::<Vec<JsonValue>>(path, &options).await {
+                Ok(page) => page,
+                Err(err) => return Some((vec![Err(err)], None)),
+            };
+
+            let size = page.len();
+            let mut current_page = HashSet::new();
+            let mut items = Vec::new();
+            for item in page {
+                let item: Result<T, Error> =
+                    serde_json::from_value(item).map_err(|_| Error::SerdeError);
+                if let (Some(_), Ok(item)) = (page_size, &item) {
+                    let item_id = id(item);
+                    let repeated = previous_page.contains(&item_id);
+                    current_page.insert(item_id);
+                    if repeated {
+                        continue;
+                    }
+                }
+                items.push(item);
+            }
+
+            let next = match page_size {
+                Some(page_size) if size == page_size && !items.is_empty() => {
+                    Some((connection, offset + page_size, current_page))
+                }
+                _ => None,
+            };
+            Some((items, next))
+        }
+    })
+    .flat_map(stream::iter)
+    .boxed()
+}
+
+impl<'a> Connection<'a> {
+    /// Stream the outputs of `public_key`, see `list_outputs`.
+    pub fn outputs_stream(
+        &self,
+        public_key: &str,
+        spent: Option<bool>,
+        options: &PageOptions,
+    ) -> BoxStream<'a, Result<TransactionOutput, Error>> {
+        let mut query = vec![("public_key", public_key.to_string())];
+        if let Some(spent) = spent {
+            query.push(("spent", spent.to_string()));
+        }
+
+        paginate(
+            self.clone(),
+            OUTPUTS,
+            query,
+            options.page_size,
+            |output: &TransactionOutput| (output.transaction_id.clone(), output.output_index),
+        )
+    }
+
+    /// Stream the transactions of the asset `asset_id`, see `list_transactions`.
+    pub fn transactions_stream(
+        &self,
+        asset_id: &str,
+        operation: Option<Operation>,
+        options: &PageOptions,
+    ) -> BoxStream<'a, Result<TransactionTemplate, Error>> {
+        let mut query = vec![("asset_id", asset_id.to_string())];
+        if let Some(operation) = operation {
+            query.push(("operation", operation.as_str().to_string()));
+        }
+
+        paginate(
+            self.clone(),
+            TRANSACTIONS,
+            query,
+            options.page_size,
+            |tx: &TransactionTemplate| tx.id.clone(),
+        )
+    }
+
+    /// Stream the outputs of `public_key` along with the transaction holding each of them.
+    ///
+    /// Transactions are fetched as the stream is polled, at most `options.concurrency` at a
+    /// time, and yielded in the order of the outputs.
+    pub fn resolved_outputs_stream(
+        &self,
+        public_key: &str,
+        spent: Option<bool>,
+        options: &PageOptions,
+    ) -> BoxStream<'a, Result<UnspentOutput, Error>> {
+        let connection = self.clone();
+
+        self.outputs_stream(public_key, spent, options)
+            .map(move |output| {
+                let connection = connection.clone();
+                async move {
+                    let output = output?;
+                    let tx = connection.get_transaction(&output.transaction_id).await?;
+                    Ok(UnspentOutput {
+                        output_index: output.output_index,
+                        tx,
+                    })
+                }
+            })
+            .buffered(options.concurrency)
+            .boxed()
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::mock::{fixtures::*, Fault, MockNode};
+
+    #[tokio::test]
+    async fn test_resolved_outputs_stream() {
+        let node = MockNode::start().await.unwrap();
+        let transactions: Vec<TransactionTemplate> = (0..5).map(create_transaction).collect();
+        for tx in transactions.iter() {
+            node.commit(tx.clone()).unwrap();
+        }
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        // five outputs over three pages
+        let options = PageOptions::new().page_size(2).concurrency(2);
+        let outputs: Vec<UnspentOutput> = conn
+            .resolved_outputs_stream(PUBLIC_KEY, Some(false), &options)
+            .try_collect()
+            .await
+            .unwrap();
+
+        let ids: Vec<_> = outputs.iter().map(|output| output.tx.id.clone()).collect();
+        let expected: Vec<_> = transactions.iter().map(|tx| tx.id.clone()).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_full_page_from_node_ignoring_paging() {
+        let node = MockNode::start().await.unwrap();
+        let transactions: Vec<TransactionTemplate> = (0..2).map(create_transaction).collect();
+        for tx in transactions.iter() {
+            node.commit(tx.clone()).unwrap();
+        }
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+        node.inject_fault(Fault::IgnorePaging);
+        node.inject_fault(Fault::IgnorePaging);
+
+        // the page is full, so the next one is requested and repeats it
+        let options = PageOptions::new().page_size(2);
+        let outputs: Vec<TransactionOutput> = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            conn.outputs_stream(PUBLIC_KEY, None, &options)
+                .try_collect(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(outputs.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_page_overlapping_the_previous_one() {
+        let node = MockNode::start().await.unwrap();
+        let creates: Vec<TransactionTemplate> = (0..4).map(create_transaction).collect();
+        for create in creates.iter() {
+            node.commit(create.clone()).unwrap();
+        }
+        for create in creates[1..].iter() {
+            node.commit(transfer_transaction(create, None)).unwrap();
+        }
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        let options = PageOptions::new().page_size(2);
+        let mut spent = conn.outputs_stream(PUBLIC_KEY, Some(true), &options);
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            ids.push(spent.try_next().await.unwrap().unwrap().transaction_id);
+        }
+
+        // spending the first output shifts the others by one, so the second page starts with
+        // the last output of the first one
+        node.commit(transfer_transaction(&creates[0], None))
+            .unwrap();
+        let rest: Vec<TransactionOutput> = spent.try_collect().await.unwrap();
+        ids.extend(rest.into_iter().map(|output| output.transaction_id));
+
+        let expected: Vec<_> = creates[1..]
+            .iter()
+            .map(|create| create.id.clone().unwrap())
+            .collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_transactions_stream() {
+        let node = MockNode::start().await.unwrap();
+        let tx = create_transaction(0);
+        node.commit(tx.clone()).unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]);
+
+        let transactions: Vec<TransactionTemplate> = conn
+            .transactions_stream(tx.id.as_ref().unwrap(), None, &PageOptions::new())
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].id, tx.id);
+    }
+}
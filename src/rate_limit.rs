@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+/// Client-side limit on the request rate sent to a node.
+///
+/// Requests are admitted by a token bucket holding up to `burst` tokens and refilled at
+/// `requests_per_second`. A request finding the bucket empty waits for its token instead of
+/// being rejected by the node with `429 Too Many Requests`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Allow `requests_per_second` on average, with bursts of as many requests.
+    pub fn per_second(requests_per_second: f64) -> Self {
+        let requests_per_second = requests_per_second.max(f64::MIN_POSITIVE);
+        Self {
+            requests_per_second,
+            burst: (requests_per_second.ceil() as u32).max(1),
+        }
+    }
+
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    // negative when requests already waiting reserved tokens not refilled yet
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            limit,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Reserve a token and return how long to wait before using it, or `None`, leaving the
+    /// bucket untouched, if that would take longer than `max_wait`.
+    pub fn reserve(&mut self, now: Instant, max_wait: Duration) -> Option<Duration> {
+        self.refill(now);
+
+        let missing = 1.0 - self.tokens;
+        let wait = if missing > 0.0 {
+            Duration::from_secs_f64(missing / self.limit.requests_per_second)
+        } else {
+            Duration::ZERO
+        };
+        if wait > max_wait {
+            return None;
+        }
+
+        self.tokens -= 1.0;
+        Some(wait)
+    }
+
+    /// Empty the bucket, e.g. once the node reports that the limit was reached anyway.
+    pub fn drain(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens = self.tokens.min(0.0);
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.limit.requests_per_second)
+            .min(self.limit.burst as f64);
+        self.last_refill = self.last_refill.max(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(RateLimit::per_second(10.0).burst(2));
+        let now = Instant::now();
+        let max_wait = Duration::from_secs(1);
+
+        assert_eq!(bucket.reserve(now, max_wait), Some(Duration::ZERO));
+        assert_eq!(bucket.reserve(now, max_wait), Some(Duration::ZERO));
+
+        // waiting requests queue up behind each other
+        let first = bucket.reserve(now, max_wait).unwrap();
+        let second = bucket.reserve(now, max_wait).unwrap();
+        assert!((first.as_secs_f64() - 0.1).abs() < 1e-6);
+        assert!((second.as_secs_f64() - 0.2).abs() < 1e-6);
+
+        // a reservation beyond the wait limit is not taken
+        assert_eq!(bucket.reserve(now, Duration::from_millis(250)), None);
+        let third = bucket.reserve(now, max_wait).unwrap();
+        assert!((third.as_secs_f64() - 0.3).abs() < 1e-6);
+
+        // refilled after a while, up to the burst
+        let later = now + Duration::from_secs(10);
+        assert_eq!(bucket.reserve(later, max_wait), Some(Duration::ZERO));
+        assert_eq!(bucket.reserve(later, max_wait), Some(Duration::ZERO));
+        assert!(bucket.reserve(later, max_wait).unwrap() > Duration::ZERO);
+    }
+}
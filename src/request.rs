@@ -4,19 +4,45 @@ use std::{
     time::{Duration, Instant},
 };
 
-use serde::de::DeserializeOwned;
-
-use crate::{error::Error, transaction::TransactionTemplate};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::{header::RETRY_AFTER, Client, Method, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::Error,
+    health::NodeStats,
+    rate_limit::{RateLimit, TokenBucket},
+    retry::RetryPolicy,
+    telemetry,
+};
 
 #[derive(Clone, Debug)]
 pub struct NormalizedNode<'a> {
     pub endpoint: &'a str,
     pub headers: Option<HashMap<&'a str, &'a str>>,
+    // nodes with a higher priority value are only used when no node with a lower one is available
+    pub priority: u32,
+    pub rate_limit: Option<RateLimit>,
 }
 
 impl<'a> NormalizedNode<'a> {
     pub fn new(endpoint: &'a str, headers: Option<HashMap<&'a str, &'a str>>) -> Self {
-        Self { endpoint, headers }
+        Self {
+            endpoint,
+            headers,
+            priority: 0,
+            rate_limit: None,
+        }
+    }
+
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
     }
 }
 
@@ -24,11 +50,105 @@ impl<'a> NormalizedNode<'a> {
 pub enum RequestMethod {
     Get,
     Post,
+    Put,
+    Patch,
+    Delete,
 }
 
-#[derive(Clone, Debug)]
+impl From<RequestMethod> for Method {
+    fn from(method: RequestMethod) -> Self {
+        match method {
+            RequestMethod::Get => Method::GET,
+            RequestMethod::Post => Method::POST,
+            RequestMethod::Put => Method::PUT,
+            RequestMethod::Patch => Method::PATCH,
+            RequestMethod::Delete => Method::DELETE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RequestBody {
+    // serialized JSON document
+    Json(Vec<u8>),
+    Raw { content_type: String, data: Vec<u8> },
+}
+
+impl RequestBody {
+    pub fn content_type(&self) -> &str {
+        match self {
+            Self::Json(_) => "application/json",
+            Self::Raw { content_type, .. } => content_type,
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Json(data) | Self::Raw { data, .. } => data,
+        }
+    }
+}
+
+// Characters percent-encoded in path parameters, so a value always stays a single segment
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Values for the `{name}` placeholders of an endpoint path such as `blocks/{block_height}`.
+#[derive(Clone, Debug, Default)]
 pub struct UrlTemplateSpec<'a> {
-    pub transaction_id: Option<&'a str>,
+    pub params: HashMap<&'a str, String>,
+}
+
+impl<'a> UrlTemplateSpec<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn param(mut self, name: &'a str, value: impl ToString) -> Self {
+        self.params.insert(name, value.to_string());
+        self
+    }
+
+    /// Replace every `{name}` placeholder of `template` with its percent-encoded value.
+    pub fn expand(&self, template: &str) -> Result<String, Error> {
+        let mut expanded = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| Error::InvalidUrlTemplate(template.to_string()))?;
+            let name = &rest[start + 1..end];
+            let value = self
+                .params
+                .get(name)
+                .ok_or_else(|| Error::MissingUrlParameter(name.to_string()))?;
+
+            // `.` and `..` would be resolved as relative path segments
+            if value.is_empty() || value == "." || value == ".." {
+                return Err(Error::InvalidUrlParameter(name.to_string()));
+            }
+
+            expanded.push_str(&rest[..start]);
+            expanded.extend(utf8_percent_encode(value, PATH_SEGMENT));
+            rest = &rest[end + 1..];
+        }
+        expanded.push_str(rest);
+
+        Ok(expanded)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -36,8 +156,10 @@ pub struct RequestOption<'a> {
     pub method: Option<RequestMethod>,
     pub query: Option<HashMap<&'a str, &'a str>>,
     pub headers: Option<HashMap<&'a str, &'a str>>,
-    pub json_body: Option<TransactionTemplate>,
+    pub body: Option<RequestBody>,
     pub url_template: Option<UrlTemplateSpec<'a>>,
+    // overrides the retry policy of the transport for this request
+    pub retry_policy: Option<RetryPolicy>,
 }
 
 impl<'a> Default for RequestOption<'a> {
@@ -52,8 +174,9 @@ impl<'a> RequestOption<'a> {
             method: None,
             query: None,
             headers: None,
-            json_body: None,
+            body: None,
             url_template: None,
+            retry_policy: None,
         }
     }
 
@@ -62,8 +185,19 @@ impl<'a> RequestOption<'a> {
         self
     }
 
-    pub fn body(mut self, body: TransactionTemplate) -> Self {
-        self.json_body = Some(body);
+    pub fn body(mut self, body: RequestBody) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Serialize `body` as the JSON body of the request.
+    pub fn json<B: Serialize + ?Sized>(self, body: &B) -> Result<Self, Error> {
+        let data = serde_json::to_vec(body).map_err(|_| Error::SerdeError)?;
+        Ok(self.body(RequestBody::Json(data)))
+    }
+
+    pub fn headers(mut self, headers: HashMap<&'a str, &'a str>) -> Self {
+        self.headers = Some(headers);
         self
     }
 
@@ -76,6 +210,11 @@ impl<'a> RequestOption<'a> {
         self.url_template = Some(spec);
         self
     }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -83,25 +222,47 @@ pub struct Request<'a> {
     pub node: NormalizedNode<'a>,
     pub backoff_time: Arc<Mutex<Option<Instant>>>,
     pub retries: Arc<Mutex<usize>>,
-    pub connection_error: Option<String>,
+    pub stats: Arc<Mutex<NodeStats>>,
+    pub limiter: Option<Arc<Mutex<TokenBucket>>>,
 }
 
 impl<'a> Request<'a> {
     pub fn new(node: NormalizedNode<'a>) -> Self {
+        let limiter = node
+            .rate_limit
+            .clone()
+            .map(|rate_limit| Arc::new(Mutex::new(TokenBucket::new(rate_limit))));
+
         Self {
             node,
             backoff_time: Arc::new(Mutex::new(Some(Instant::now()))),
             retries: Arc::new(Mutex::new(0)),
-            connection_error: None,
+            stats: Arc::new(Mutex::new(NodeStats::default())),
+            limiter,
         }
     }
 
+    pub fn set_rate_limit(&mut self, rate_limit: RateLimit) {
+        self.limiter = Some(Arc::new(Mutex::new(TokenBucket::new(rate_limit.clone()))));
+        self.node.rate_limit = Some(rate_limit);
+    }
+
+    #[tracing::instrument(
+        name = "bigchaindb.attempt",
+        skip_all,
+        fields(
+            node = self.node.endpoint,
+            path = url_path,
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        )
+    )]
     pub async fn request<T: DeserializeOwned>(
         &self,
+        client: &Client,
         url_path: &str,
         config: &RequestOption<'_>,
         timeout: Duration,
-        max_backoff_time: Duration,
     ) -> Result<T, Error> {
         let mut request_headers: HashMap<&str, &str> = HashMap::new();
         if let Some(headers) = &self.node.headers {
@@ -109,8 +270,8 @@ impl<'a> Request<'a> {
         }
         request_headers.insert("Accept", "application/json");
 
-        if config.json_body.is_some() {
-            request_headers.insert("Content-Type", "application/json");
+        if let Some(body) = &config.body {
+            request_headers.insert("Content-Type", body.content_type());
         }
 
         if let Some(headers) = &config.headers {
@@ -120,22 +281,22 @@ impl<'a> Request<'a> {
         let mut request_config = config.clone();
         request_config.headers = Some(request_headers);
 
+        // `url_path` stays the template, the label of the attempt metrics
+        let expanded = match &config.url_template {
+            Some(spec) => spec.expand(url_path)?,
+            None => UrlTemplateSpec::new().expand(url_path)?,
+        };
         let api_url = format!(
-            "{node_endpoint}{url_path}",
+            "{node_endpoint}{expanded}",
             node_endpoint = self.node.endpoint
         );
 
-        // If connectionError occurs, a timestamp equal to now +
-        // `backoffTimedelta` is assigned to the object.
-        // Next time the function is called, it either
-        // waits till the timestamp is passed or raises `TimeoutError`.
-        // If `ConnectionError` occurs two or more times in a row,
-        // the retry count is incremented and the new timestamp is calculated
-        // as now + the `backoffTimedelta`
-        // The `backoffTimedelta` is the minimum between the default delay
-        // multiplied by two to the power of the
-        // number of retries or timeout/2 or 10. See Transport class for that
-        // If a request is successful, the backoff timestamp is removed,
+        // When a request fails, the `Transport` calls `record_failure` which assigns a
+        // timestamp equal to now + the backoff delay of the retry policy to the node.
+        // Next time the node is used, the request either waits till the timestamp is
+        // passed or raises `RequestTimeout` if the timestamp is beyond `timeout`.
+        // The delay grows exponentially with the number of failures in a row.
+        // If a request is successful, the backoff timestamp is removed and
         // the retry count is back to zero.
 
         let backoff_time_delta = self.get_backoff_time_delta();
@@ -152,14 +313,79 @@ impl<'a> Request<'a> {
             .checked_sub(backoff_time_delta)
             .unwrap_or(Duration::new(0, 0));
 
-        let resp: T = base_request(&api_url, request_config, Some(request_timeout)).await?;
+        // Wait for a token of the rate limit of the node, if any, rather than being answered
+        // `429 Too Many Requests`.
+        let rate_limit_delay = self.reserve_token(request_timeout)?;
+        if !rate_limit_delay.is_zero() {
+            tracing::debug!(
+                delay_ms = rate_limit_delay.as_millis() as u64,
+                "waiting for rate limit"
+            );
+            tokio::time::sleep(rate_limit_delay).await;
+        }
+        let request_timeout = request_timeout.saturating_sub(rate_limit_delay);
+
+        let start_time = Instant::now();
+        let resp = base_request(client, &api_url, request_config, Some(request_timeout)).await;
+        let latency = start_time.elapsed();
+        self.record_response(&resp, latency);
+
+        tracing::Span::current().record("latency_ms", latency.as_millis() as u64);
+        match &resp {
+            Ok(_) => tracing::debug!("request attempt succeeded"),
+            Err(err) => tracing::debug!(error = %err, "request attempt failed"),
+        }
+        telemetry::record_attempt(self.node.endpoint, url_path, latency, resp.is_ok());
+
+        resp
+    }
+
+    /// Request the API root of the node and record whether it answered.
+    pub async fn probe(&self, client: &Client, timeout: Duration) -> bool {
+        let mut options = RequestOption::new().method(RequestMethod::Get);
+        options.headers = self.node.headers.clone();
 
-        self.update_backoff_time(max_backoff_time);
+        let start_time = Instant::now();
+        let resp: Result<serde_json::Value, Error> =
+            base_request(client, self.node.endpoint, options, Some(timeout)).await;
+        self.record_response(&resp, start_time.elapsed());
+
+        let mut stats = self.stats.lock().unwrap();
+        stats.healthy = resp.is_ok();
+        stats.last_probe = Some(Instant::now());
+
+        stats.healthy
+    }
+
+    // Client errors still prove that the node is up, only count connection failures,
+    // timeouts and server errors against it.
+    fn record_response<T>(&self, resp: &Result<T, Error>, latency: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        match resp {
+            Err(Error::RequestError | Error::RequestTimeout) => stats.record_failure(),
+            Err(Error::ResponseError { status, .. }) if *status >= 500 => stats.record_failure(),
+            _ => stats.record_success(latency),
+        }
+    }
+
+    /// Whether the node is healthy and not backing off after a failure.
+    pub fn is_available(&self, now: Instant) -> bool {
+        let backoff_time = *self.backoff_time.lock().unwrap();
+        self.stats.lock().unwrap().healthy && backoff_time.map(|time| time <= now).unwrap_or(true)
+    }
 
-        Ok(resp)
+    fn reserve_token(&self, timeout: Duration) -> Result<Duration, Error> {
+        match &self.limiter {
+            Some(limiter) => limiter
+                .lock()
+                .unwrap()
+                .reserve(Instant::now(), timeout)
+                .ok_or_else(|| Error::RateLimitExceeded(self.node.endpoint.to_string())),
+            None => Ok(Duration::ZERO),
+        }
     }
 
-    fn get_backoff_time_delta(&self) -> Duration {
+    pub(crate) fn get_backoff_time_delta(&self) -> Duration {
         if let Some(value) = *self.backoff_time.lock().unwrap() {
             value.duration_since(Instant::now())
         } else {
@@ -167,66 +393,311 @@ impl<'a> Request<'a> {
         }
     }
 
-    fn update_backoff_time(&self, _max_backoff_time: Duration) {
-        if self.connection_error.is_none() {
-            *self.backoff_time.lock().unwrap() = None;
-            *self.retries.lock().unwrap() = 0;
+    pub fn record_success(&self) {
+        *self.backoff_time.lock().unwrap() = None;
+        *self.retries.lock().unwrap() = 0;
+    }
+
+    pub fn record_failure(&self, retry_policy: &RetryPolicy) {
+        let mut retries = self.retries.lock().unwrap();
+        *retries += 1;
+
+        let delay = retry_policy.backoff_delay(*retries as u32);
+        *self.backoff_time.lock().unwrap() = Some(Instant::now() + delay);
+    }
+
+    /// Back off for at least `retry_after` after the node answered `429 Too Many Requests`.
+    pub fn record_rate_limited(&self, retry_after: Option<Duration>) {
+        let now = Instant::now();
+        if let Some(limiter) = &self.limiter {
+            limiter.lock().unwrap().drain(now);
+        }
+
+        if let Some(retry_after) = retry_after {
+            let mut backoff_time = self.backoff_time.lock().unwrap();
+            *backoff_time = (*backoff_time).max(Some(now + retry_after));
         }
     }
 }
 
 pub async fn base_request<T: DeserializeOwned>(
+    client: &Client,
     api_url: &str,
     request_config: RequestOption<'_>,
     request_timeout: Option<Duration>,
 ) -> Result<T, Error> {
-    let mut expanded_url = api_url.to_string();
-
-    if let Some(url_template) = request_config.url_template {
-        if let Some(transaction_id) = url_template.transaction_id {
-            expanded_url = expanded_url.replace("{transaction_id}", transaction_id);
-        }
+    let method = request_config.method.unwrap_or(RequestMethod::Get);
+    if method == RequestMethod::Post && request_config.body.is_none() {
+        return Err(Error::RequestNoBodyProvided);
     }
 
-    let mut client = if request_config.method.unwrap_or(RequestMethod::Get) == RequestMethod::Post {
-        let body = serde_json::to_string(
-            &request_config
-                .json_body
-                .ok_or(Error::RequestNoBodyProvided)?,
-        )
-        .map_err(|_| Error::SerdeError)?;
+    let mut request = client.request(method.into(), api_url);
 
-        reqwest::Client::new().post(expanded_url).body(body)
-    } else {
-        reqwest::Client::new().get(expanded_url)
-    };
+    if let Some(body) = request_config.body {
+        request = request.body(body.into_bytes());
+    }
 
     if let Some(query) = request_config.query {
-        client = client.query(&query);
+        request = request.query(&query);
     }
 
     if let Some(headers) = request_config.headers {
         for (key, value) in headers.iter() {
-            client = client.header(*key, *value);
+            request = request.header(*key, *value);
         }
     }
 
     if let Some(timeout) = request_timeout {
-        client = client.timeout(timeout);
+        request = request.timeout(timeout);
     }
 
-    let resp = client.send().await.map_err(|_| Error::RequestError)?;
+    let resp = request.send().await.map_err(|err| {
+        if err.is_timeout() {
+            Error::RequestTimeout
+        } else {
+            Error::RequestError
+        }
+    })?;
+
+    let status = resp.status();
+    tracing::Span::current().record("status", status.as_u16());
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = resp
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        return Err(Error::RateLimited { retry_after });
+    }
+    let body = resp.bytes().await.map_err(|err| {
+        if err.is_timeout() {
+            Error::RequestTimeout
+        } else {
+            Error::RequestError
+        }
+    })?;
 
-    match resp.error_for_status() {
-        Ok(data) => Ok(data.json::<T>().await.map_err(|_| Error::SerdeError)?),
-        Err(err) => {
-            if err.is_timeout() {
-                return Err(Error::RequestTimeout);
-            }
+    if !status.is_success() {
+        return Err(response_error(status.as_u16(), &body));
+    }
+
+    // an empty body, e.g. from a `DELETE`, is read as JSON `null`
+    let body: &[u8] = if body.is_empty() { b"null" } else { &body };
+    serde_json::from_slice(body).map_err(|_| Error::SerdeError)
+}
+
+// `Retry-After` is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
 
-            // TODO: handle all errors from reqwest's response
+// BigchainDB answers errors with `{"message": ..., "status": ...}`
+fn response_error(status: u16, body: &[u8]) -> Error {
+    let message = serde_json::from_slice::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| json.get("message")?.as_str().map(String::from))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).into_owned());
 
-            Err(Error::ResponseError)
+    if status == 400 && message.contains("DuplicateTransaction") {
+        return Error::DuplicateTransaction { retried: false };
+    }
+
+    Error::ResponseError { status, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_request_option_json_body() {
+        let options = RequestOption::new()
+            .method(RequestMethod::Put)
+            .json(&json!({"b": 1, "a": [true]}))
+            .unwrap();
+
+        assert_eq!(
+            options.body,
+            Some(RequestBody::Json(br#"{"a":[true],"b":1}"#.to_vec()))
+        );
+        assert_eq!(options.body.unwrap().content_type(), "application/json");
+    }
+
+    #[test]
+    fn test_response_error() {
+        let body = br#"{"message": "Invalid transaction (DuplicateTransaction): transaction `a` already exists", "status": 400}"#;
+        assert!(matches!(
+            response_error(400, body),
+            Error::DuplicateTransaction { retried: false }
+        ));
+
+        assert!(matches!(
+            response_error(502, b"Bad Gateway"),
+            Error::ResponseError { status: 502, message } if message == "Bad Gateway"
+        ));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        let delay = parse_retry_after(&date).unwrap();
+        assert!(delay > Duration::from_secs(25) && delay <= Duration::from_secs(30));
+
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_expand_url_template() {
+        let spec = UrlTemplateSpec::new()
+            .param("transaction_id", "abc")
+            .param("block_height", 12);
+
+        assert_eq!(
+            spec.expand("transactions/{transaction_id}").unwrap(),
+            "transactions/abc"
+        );
+        assert_eq!(spec.expand("blocks/{block_height}").unwrap(), "blocks/12");
+        assert_eq!(
+            spec.expand("transactions?mode=sync").unwrap(),
+            "transactions?mode=sync"
+        );
+    }
+
+    #[test]
+    fn test_expand_url_template_encodes_values() {
+        let spec = UrlTemplateSpec::new().param("transaction_id", "../outputs?public_key=a b");
+
+        assert_eq!(
+            spec.expand("transactions/{transaction_id}").unwrap(),
+            "transactions/..%2Foutputs%3Fpublic_key=a%20b"
+        );
+    }
+
+    #[test]
+    fn test_expand_url_template_errors() {
+        let spec = UrlTemplateSpec::new().param("transaction_id", "..");
+
+        assert!(matches!(
+            spec.expand("blocks/{block_height}"),
+            Err(Error::MissingUrlParameter(name)) if name == "block_height"
+        ));
+        assert!(matches!(
+            spec.expand("transactions/{transaction_id}"),
+            Err(Error::InvalidUrlParameter(name)) if name == "transaction_id"
+        ));
+        assert!(matches!(
+            spec.expand("transactions/{transaction_id"),
+            Err(Error::InvalidUrlTemplate(_))
+        ));
+    }
+
+    // Keeps the name and labels of every counter registered while it is the local recorder.
+    #[cfg(all(feature = "metrics", feature = "mock"))]
+    #[derive(Default)]
+    struct CounterRecorder(Mutex<Vec<metrics::Key>>);
+
+    #[cfg(all(feature = "metrics", feature = "mock"))]
+    impl metrics::Recorder for CounterRecorder {
+        fn describe_counter(
+            &self,
+            _key: metrics::KeyName,
+            _unit: Option<metrics::Unit>,
+            _description: metrics::SharedString,
+        ) {
+        }
+
+        fn describe_gauge(
+            &self,
+            _key: metrics::KeyName,
+            _unit: Option<metrics::Unit>,
+            _description: metrics::SharedString,
+        ) {
+        }
+
+        fn describe_histogram(
+            &self,
+            _key: metrics::KeyName,
+            _unit: Option<metrics::Unit>,
+            _description: metrics::SharedString,
+        ) {
         }
+
+        fn register_counter(
+            &self,
+            key: &metrics::Key,
+            _metadata: &metrics::Metadata<'_>,
+        ) -> metrics::Counter {
+            self.0.lock().unwrap().push(key.clone());
+            metrics::Counter::noop()
+        }
+
+        fn register_gauge(
+            &self,
+            _key: &metrics::Key,
+            _metadata: &metrics::Metadata<'_>,
+        ) -> metrics::Gauge {
+            metrics::Gauge::noop()
+        }
+
+        fn register_histogram(
+            &self,
+            _key: &metrics::Key,
+            _metadata: &metrics::Metadata<'_>,
+        ) -> metrics::Histogram {
+            metrics::Histogram::noop()
+        }
+    }
+
+    #[cfg(all(feature = "metrics", feature = "mock"))]
+    #[test]
+    fn test_attempt_endpoint_label_is_the_template() {
+        use crate::{
+            connection::Connection,
+            mock::{fixtures::create_transaction, MockNode},
+        };
+
+        let recorder = CounterRecorder::default();
+        // a single thread, so that the node and the requests all see the local recorder
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let node = MockNode::start().await.unwrap();
+                let tx = create_transaction(0);
+                node.commit(tx.clone()).unwrap();
+                let endpoint = node.endpoint();
+                let conn = Connection::new(vec![endpoint.as_str()]);
+                conn.get_transaction(tx.id.as_ref().unwrap()).await.unwrap();
+            })
+        });
+
+        let counters = recorder.0.lock().unwrap();
+        let attempts = counters
+            .iter()
+            .find(|key| key.name() == "bigchaindb_attempts_total")
+            .unwrap();
+        let endpoint = attempts
+            .labels()
+            .find(|label| label.key() == "endpoint")
+            .unwrap();
+        assert_eq!(endpoint.value(), "transactions/{transaction_id}");
     }
 }
@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::error::Error;
+
+const DEFAULT_MAX_ATTEMPTS: usize = 5;
+const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(10);
+const DEFAULT_JITTER: f64 = 0.2;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(20);
+const DEFAULT_RETRYABLE_STATUSES: [u16; 4] = [408, 502, 503, 504];
+
+/// How a `Transport` retries a request that failed with a connection error, a timeout, a
+/// `429 Too Many Requests` or one of the `retryable_statuses`.
+///
+/// Every failure backs the failing node off for `base_delay * 2^(failures - 1)`, capped at
+/// `max_delay` and randomized by `jitter`, and the next attempt goes to the node whose backoff
+/// ends first. `timeout` bounds the whole request, retries and waits included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // fraction of the delay, in [0, 1], by which it is randomly shortened or lengthened
+    pub jitter: f64,
+    pub timeout: Duration,
+    pub retryable_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            jitter: DEFAULT_JITTER,
+            timeout: DEFAULT_TIMEOUT,
+            retryable_statuses: DEFAULT_RETRYABLE_STATUSES.to_vec(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy making a single attempt.
+    pub fn no_retry() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn retryable_statuses(mut self, statuses: Vec<u16>) -> Self {
+        self.retryable_statuses = statuses;
+        self
+    }
+
+    /// Delay before a node that failed `failures` times in a row may be used again.
+    pub fn backoff_delay(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        if self.jitter > 0.0 {
+            let factor = rand::thread_rng().gen_range(-self.jitter..=self.jitter);
+            delay.mul_f64(1.0 + factor).min(self.max_delay)
+        } else {
+            delay
+        }
+    }
+
+    pub fn is_retryable(&self, error: &Error) -> bool {
+        match error {
+            Error::RequestError | Error::RequestTimeout | Error::RateLimited { .. } => true,
+            Error::ResponseError { status, .. } => self.retryable_statuses.contains(status),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(1000))
+            .jitter(0.0);
+
+        assert_eq!(policy.backoff_delay(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff_delay(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff_delay(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff_delay(5), Duration::from_millis(1000));
+        assert_eq!(policy.backoff_delay(100), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_backoff_delay_with_jitter() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_millis(100))
+            .jitter(0.5);
+
+        for _ in 0..100 {
+            let delay = policy.backoff_delay(1);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let policy = RetryPolicy::new();
+
+        assert!(policy.is_retryable(&Error::RequestError));
+        assert!(policy.is_retryable(&Error::ResponseError {
+            status: 503,
+            message: String::new(),
+        }));
+        assert!(!policy.is_retryable(&Error::ResponseError {
+            status: 404,
+            message: String::new(),
+        }));
+        assert!(policy.is_retryable(&Error::RateLimited { retry_after: None }));
+        assert!(!policy.is_retryable(&Error::SerdeError));
+        assert!(!policy.is_retryable(&Error::RateLimitExceeded(String::new())));
+    }
+}
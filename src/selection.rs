@@ -0,0 +1,165 @@
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use rand::Rng;
+
+use crate::request::Request;
+
+/// Strategy used by a `Transport` to choose the node of the next request.
+///
+/// Candidates are the healthy nodes that are not backing off, restricted to the lowest
+/// priority present, so fallback nodes are only offered when no primary node is available.
+pub trait NodeSelector: Debug + Send + Sync {
+    /// Return the index in `candidates` of the node to use. `candidates` is never empty.
+    fn select(&self, candidates: &[&Request]) -> usize;
+}
+
+/// Cycle through the candidates.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl NodeSelector for RoundRobin {
+    fn select(&self, candidates: &[&Request]) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % candidates.len()
+    }
+}
+
+/// Use the candidate with the lowest average latency. Nodes without samples yet are tried
+/// first.
+#[derive(Debug, Default)]
+pub struct LeastLatency;
+
+impl NodeSelector for LeastLatency {
+    fn select(&self, candidates: &[&Request]) -> usize {
+        candidates
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, connection)| {
+                connection
+                    .stats
+                    .lock()
+                    .unwrap()
+                    .latency
+                    .unwrap_or(Duration::ZERO)
+            })
+            .map(|(index, _)| index)
+            .unwrap_or(0)
+    }
+}
+
+/// Keep using the same node as long as it is a candidate.
+#[derive(Debug, Default)]
+pub struct Sticky {
+    current: Mutex<Option<String>>,
+}
+
+impl NodeSelector for Sticky {
+    fn select(&self, candidates: &[&Request]) -> usize {
+        let mut current = self.current.lock().unwrap();
+
+        let index = current
+            .as_ref()
+            .and_then(|endpoint| {
+                candidates
+                    .iter()
+                    .position(|connection| connection.node.endpoint == endpoint.as_str())
+            })
+            .unwrap_or(0);
+        *current = Some(candidates[index].node.endpoint.to_string());
+
+        index
+    }
+}
+
+/// Pick a candidate at random.
+#[derive(Debug, Default)]
+pub struct Random;
+
+impl NodeSelector for Random {
+    fn select(&self, candidates: &[&Request]) -> usize {
+        rand::thread_rng().gen_range(0..candidates.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::NormalizedNode;
+
+    fn requests() -> Vec<Request<'static>> {
+        ["http://a/", "http://b/", "http://c/"]
+            .into_iter()
+            .map(|endpoint| Request::new(NormalizedNode::new(endpoint, None)))
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let requests = requests();
+        let candidates: Vec<&Request> = requests.iter().collect();
+        let selector = RoundRobin::default();
+
+        let selected: Vec<usize> = (0..4).map(|_| selector.select(&candidates)).collect();
+        assert_eq!(selected, vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_least_latency() {
+        let requests = requests();
+        requests[0]
+            .stats
+            .lock()
+            .unwrap()
+            .record_success(Duration::from_millis(300));
+        requests[1]
+            .stats
+            .lock()
+            .unwrap()
+            .record_success(Duration::from_millis(20));
+        requests[2]
+            .stats
+            .lock()
+            .unwrap()
+            .record_success(Duration::from_millis(90));
+        let candidates: Vec<&Request> = requests.iter().collect();
+
+        assert_eq!(LeastLatency.select(&candidates), 1);
+    }
+
+    #[test]
+    fn test_sticky() {
+        let requests = requests();
+        let selector = Sticky::default();
+
+        let candidates: Vec<&Request> = requests[1..].iter().collect();
+        assert_eq!(selector.select(&candidates), 0);
+
+        // stays on `b` even when `a` comes back
+        let candidates: Vec<&Request> = requests.iter().collect();
+        assert_eq!(selector.select(&candidates), 1);
+
+        // moves on when `b` is gone
+        let candidates: Vec<&Request> = vec![&requests[0], &requests[2]];
+        assert_eq!(selector.select(&candidates), 0);
+        let candidates: Vec<&Request> = requests.iter().collect();
+        assert_eq!(selector.select(&candidates), 0);
+    }
+
+    #[test]
+    fn test_random() {
+        let requests = requests();
+        let candidates: Vec<&Request> = requests.iter().collect();
+
+        for _ in 0..20 {
+            assert!(Random.select(&candidates) < candidates.len());
+        }
+    }
+}
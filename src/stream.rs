@@ -0,0 +1,475 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::{error::Error, health::NodeStats, retry::RetryPolicy};
+
+pub const STREAMS_VALID_TRANSACTIONS: &str = "streams/valid_transactions";
+
+// delay before the first reconnection round after every node failed, doubled on each round
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+// failed connections in a row after which a node is asked for its stream endpoint again
+const REDISCOVERY_FAILURES: u32 = 3;
+
+/// Event sent by a node for every transaction committed to a block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ValidTransactionEvent {
+    pub transaction_id: String,
+    pub asset_id: String,
+    pub height: u64,
+}
+
+/// Guess the WebSocket endpoint of the valid transactions stream from an HTTP API endpoint
+/// such as `http://localhost:9984/api/v1/`, on the same host and port. Nodes usually serve the
+/// stream on a port of its own, see `discover_valid_transactions_endpoint`.
+pub fn valid_transactions_endpoint(api_endpoint: &str) -> String {
+    let endpoint = if let Some(rest) = api_endpoint.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = api_endpoint.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        api_endpoint.to_string()
+    };
+
+    if endpoint.ends_with('/') {
+        format!("{endpoint}{STREAMS_VALID_TRANSACTIONS}")
+    } else {
+        format!("{endpoint}/{STREAMS_VALID_TRANSACTIONS}")
+    }
+}
+
+/// Ask the node at `api_endpoint` for the WebSocket endpoint of its valid transactions stream,
+/// advertised as `streams` in its API root.
+pub async fn discover_valid_transactions_endpoint(
+    client: &Client,
+    api_endpoint: &str,
+) -> Option<String> {
+    let root: JsonValue = client
+        .get(api_endpoint)
+        .timeout(DISCOVERY_TIMEOUT)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+
+    let streams = root.get("streams")?.as_str()?;
+    (streams.starts_with("ws://") || streams.starts_with("wss://")).then(|| streams.to_string())
+}
+
+/// Where a subscription connects.
+enum Target {
+    Stream(String),
+    // a node whose stream endpoint is discovered on connection, and kept once found
+    Node {
+        client: Client,
+        api_endpoint: String,
+        stream: Option<String>,
+        // statistics of the node shared with the transport, if any
+        stats: Option<Arc<Mutex<NodeStats>>>,
+        failures: u32,
+    },
+}
+
+impl Target {
+    async fn endpoint(&mut self) -> String {
+        match self {
+            Self::Stream(endpoint) => endpoint.clone(),
+            Self::Node {
+                client,
+                api_endpoint,
+                stream,
+                ..
+            } => {
+                if stream.is_none() {
+                    *stream = discover_valid_transactions_endpoint(client, api_endpoint).await;
+                }
+                stream
+                    .clone()
+                    .unwrap_or_else(|| valid_transactions_endpoint(api_endpoint))
+            }
+        }
+    }
+
+    fn record_success(&mut self) {
+        if let Self::Node { failures, .. } = self {
+            *failures = 0;
+        }
+    }
+
+    // A node that did not answer in time is marked failed, and one that keeps failing is asked
+    // for its stream endpoint again, in case it moved.
+    fn record_failure(&mut self, timed_out: bool) {
+        if let Self::Node {
+            stream,
+            stats,
+            failures,
+            ..
+        } = self
+        {
+            if let (true, Some(stats)) = (timed_out, stats) {
+                stats.lock().unwrap().record_failure();
+            }
+
+            *failures += 1;
+            if *failures >= REDISCOVERY_FAILURES {
+                *stream = None;
+                *failures = 0;
+            }
+        }
+    }
+}
+
+struct Subscription {
+    endpoints: Vec<Target>,
+    next_endpoint: usize,
+    socket: Option<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    retries: u32,
+    connect_timeout: Duration,
+}
+
+impl Subscription {
+    // Try every endpoint in turn until one accepts the connection, backing off after each full
+    // round of failures.
+    async fn connect(&mut self) {
+        loop {
+            let target = &mut self.endpoints[self.next_endpoint];
+            let endpoint = target.endpoint().await;
+
+            match tokio::time::timeout(self.connect_timeout, connect_async(endpoint.as_str())).await
+            {
+                Ok(Ok((socket, _))) => {
+                    target.record_success();
+                    self.socket = Some(socket);
+                    self.retries = 0;
+                    self.next_endpoint = (self.next_endpoint + 1) % self.endpoints.len();
+                    return;
+                }
+                Ok(Err(_)) => target.record_failure(false),
+                Err(_) => target.record_failure(true),
+            }
+            self.next_endpoint = (self.next_endpoint + 1) % self.endpoints.len();
+
+            if self.next_endpoint == 0 {
+                let delay = RECONNECT_DELAY
+                    .saturating_mul(2u32.saturating_pow(self.retries))
+                    .min(MAX_RECONNECT_DELAY);
+                self.retries += 1;
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Subscribe to the valid transactions stream served at `endpoints`.
+///
+/// The stream never ends: when the socket is closed or fails, it reconnects to the next
+/// endpoint. Messages that are not valid events are yielded as `Error::SerdeError`.
+pub fn subscribe_valid_transactions(
+    endpoints: Vec<String>,
+) -> BoxStream<'static, Result<ValidTransactionEvent, Error>> {
+    subscribe(
+        endpoints.into_iter().map(Target::Stream).collect(),
+        RetryPolicy::default().timeout,
+    )
+}
+
+/// Subscribe to the valid transactions stream of the nodes at `api_endpoints`, each connecting
+/// to the stream its API root advertises or, when it advertises none, to
+/// `valid_transactions_endpoint`.
+pub fn subscribe_valid_transactions_of_nodes(
+    client: Client,
+    api_endpoints: Vec<String>,
+) -> BoxStream<'static, Result<ValidTransactionEvent, Error>> {
+    subscribe_nodes(
+        client,
+        api_endpoints
+            .into_iter()
+            .map(|api_endpoint| (api_endpoint, None))
+            .collect(),
+        RetryPolicy::default().timeout,
+    )
+}
+
+// Subscribe to the nodes at the given API endpoints, marking a node failed in its statistics
+// when its stream does not accept the connection within `connect_timeout`.
+pub(crate) fn subscribe_nodes(
+    client: Client,
+    nodes: Vec<(String, Option<Arc<Mutex<NodeStats>>>)>,
+    connect_timeout: Duration,
+) -> BoxStream<'static, Result<ValidTransactionEvent, Error>> {
+    subscribe(
+        nodes
+            .into_iter()
+            .map(|(api_endpoint, stats)| Target::Node {
+                client: client.clone(),
+                api_endpoint,
+                stream: None,
+                stats,
+                failures: 0,
+            })
+            .collect(),
+        connect_timeout,
+    )
+}
+
+fn subscribe(
+    endpoints: Vec<Target>,
+    connect_timeout: Duration,
+) -> BoxStream<'static, Result<ValidTransactionEvent, Error>> {
+    if endpoints.is_empty() {
+        return stream::empty().boxed();
+    }
+
+    let subscription = Subscription {
+        endpoints,
+        next_endpoint: 0,
+        socket: None,
+        retries: 0,
+        connect_timeout,
+    };
+
+    stream::unfold(subscription, |mut subscription| async move {
+        loop {
+            let Some(socket) = subscription.socket.as_mut() else {
+                subscription.connect().await;
+                continue;
+            };
+
+            let message = socket.next().await;
+            match message {
+                Some(Ok(Message::Text(text))) => {
+                    let event = serde_json::from_str(&text).map_err(|_| Error::SerdeError);
+                    return Some((event, subscription));
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    subscription.socket = None;
+                }
+                Some(Ok(_)) => {}
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use tokio_tungstenite::accept_async;
+
+    use super::*;
+    use crate::connection::Connection;
+
+    fn event(height: u64) -> ValidTransactionEvent {
+        ValidTransactionEvent {
+            transaction_id: format!("tx{height}"),
+            asset_id: String::from("asset"),
+            height,
+        }
+    }
+
+    // Serve each batch of events on its own connection, closing the socket after each batch.
+    // Requests that are not WebSocket handshakes, such as the discovery of the stream, fail.
+    async fn serve(listener: TcpListener, batches: Vec<Vec<ValidTransactionEvent>>) {
+        for batch in batches {
+            let mut socket = loop {
+                let (tcp, _) = listener.accept().await.unwrap();
+                if let Ok(socket) = accept_async(tcp).await {
+                    break socket;
+                }
+            };
+            for event in batch {
+                let text = serde_json::to_string(&event).unwrap();
+                socket.send(Message::Text(text)).await.unwrap();
+            }
+            socket.close(None).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_valid_transactions_endpoint() {
+        assert_eq!(
+            valid_transactions_endpoint("http://localhost:9985/api/v1/"),
+            "ws://localhost:9985/api/v1/streams/valid_transactions"
+        );
+        assert_eq!(
+            valid_transactions_endpoint("https://example.com/api/v1"),
+            "wss://example.com/api/v1/streams/valid_transactions"
+        );
+    }
+
+    // Answer one HTTP request with `body` as JSON.
+    async fn serve_api_root(listener: TcpListener, body: JsonValue) {
+        let (mut tcp, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let read = tcp.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+        }
+
+        let body = body.to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        tcp.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_discover_advertised_stream() {
+        let api = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/api/v1/", api.local_addr().unwrap());
+        let stream_endpoint = "ws://127.0.0.1:9985/api/v1/streams/valid_transactions";
+
+        tokio::spawn(serve_api_root(
+            api,
+            serde_json::json!({ "streams": stream_endpoint, "version": "2.2.2" }),
+        ));
+
+        let discovered = discover_valid_transactions_endpoint(&Client::new(), &endpoint).await;
+        assert_eq!(discovered.as_deref(), Some(stream_endpoint));
+    }
+
+    #[tokio::test]
+    async fn test_subscription_connects_to_discovered_stream() {
+        let api = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/api/v1/", api.local_addr().unwrap());
+        let streams = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream_endpoint = format!(
+            "ws://{}/api/v1/{STREAMS_VALID_TRANSACTIONS}",
+            streams.local_addr().unwrap()
+        );
+
+        tokio::spawn(serve_api_root(
+            api,
+            serde_json::json!({ "streams": stream_endpoint }),
+        ));
+        tokio::spawn(serve(streams, vec![vec![event(5)]]));
+
+        let conn = Connection::new(vec![endpoint.as_str()]);
+        let mut events = conn.subscribe_valid_transactions();
+        assert_eq!(events.next().await.unwrap().unwrap(), event(5));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_reconnects_after_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/api/v1/", listener.local_addr().unwrap());
+        tokio::spawn(serve(
+            listener,
+            vec![vec![event(1), event(2)], vec![event(3)]],
+        ));
+
+        let conn = Connection::new(vec![endpoint.as_str()]);
+        let events: Vec<_> = conn
+            .subscribe_valid_transactions()
+            .take(3)
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(events, vec![event(1), event(2), event(3)]);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_times_out_unresponsive_stream() {
+        // a stream that accepts connections but never answers the handshake
+        let hanging = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hanging_endpoint = format!("ws://{}/", hanging.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            loop {
+                sockets.push(hanging.accept().await.unwrap());
+            }
+        });
+
+        let api = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_endpoint = format!("http://{}/api/v1/", api.local_addr().unwrap());
+        tokio::spawn(serve_api_root(
+            api,
+            serde_json::json!({ "streams": hanging_endpoint }),
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/api/v1/", listener.local_addr().unwrap());
+        tokio::spawn(serve(listener, vec![vec![event(4)]]));
+
+        let conn = Connection::new(vec![api_endpoint.as_str(), endpoint.as_str()])
+            .with_retry_policy(RetryPolicy::default().timeout(Duration::from_millis(200)));
+        let mut events = conn.subscribe_valid_transactions();
+
+        let next = tokio::time::timeout(Duration::from_secs(5), events.next()).await;
+        assert_eq!(next.unwrap().unwrap().unwrap(), event(4));
+        assert_eq!(conn.node_health()[0].failures, 1);
+    }
+
+    #[test]
+    fn test_failing_node_rediscovers_stream() {
+        let stats = Arc::new(Mutex::new(NodeStats::default()));
+        let mut target = Target::Node {
+            client: Client::new(),
+            api_endpoint: String::from("http://localhost:9984/api/v1/"),
+            stream: Some(String::from(
+                "ws://localhost:9985/api/v1/streams/valid_transactions",
+            )),
+            stats: Some(stats.clone()),
+            failures: 0,
+        };
+
+        for _ in 1..REDISCOVERY_FAILURES {
+            target.record_failure(true);
+        }
+        assert!(matches!(
+            &target,
+            Target::Node {
+                stream: Some(_),
+                ..
+            }
+        ));
+
+        target.record_failure(false);
+        assert!(matches!(&target, Target::Node { stream: None, .. }));
+        assert_eq!(
+            stats.lock().unwrap().failures,
+            u64::from(REDISCOVERY_FAILURES) - 1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_fails_over_to_next_node() {
+        // bind then drop a listener to get an address nothing listens on
+        let dead = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_endpoint = format!("http://{}/api/v1/", dead.local_addr().unwrap());
+        drop(dead);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/api/v1/", listener.local_addr().unwrap());
+        tokio::spawn(serve(listener, vec![vec![event(7)]]));
+
+        let conn = Connection::new(vec![dead_endpoint.as_str(), endpoint.as_str()]);
+        let mut events = conn.subscribe_valid_transactions();
+
+        assert_eq!(events.next().await.unwrap().unwrap(), event(7));
+    }
+}
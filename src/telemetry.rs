@@ -0,0 +1,67 @@
+//! Metrics about the requests sent to the nodes.
+//!
+//! With the `metrics` feature enabled, they are reported through the `metrics` facade to
+//! whichever recorder the application installed. Without it, every function is a no-op.
+//!
+//! | name                                  | type      | labels                        |
+//! |---------------------------------------|-----------|-------------------------------|
+//! | `bigchaindb_attempts_total`           | counter   | `node`, `endpoint`, `outcome` |
+//! | `bigchaindb_attempt_duration_seconds` | histogram | `node`, `endpoint`            |
+//! | `bigchaindb_requests_total`           | counter   | `endpoint`, `outcome`         |
+//! | `bigchaindb_request_attempts`         | histogram | `endpoint`                    |
+//!
+//! `endpoint` is the path template, e.g. `transactions/{transaction_id}`, so that it does not
+//! grow with the number of transactions.
+
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+fn outcome(success: bool) -> &'static str {
+    if success {
+        "success"
+    } else {
+        "failure"
+    }
+}
+
+/// Record a single attempt of a request against `node`.
+#[cfg(feature = "metrics")]
+pub fn record_attempt(node: &str, endpoint: &str, latency: Duration, success: bool) {
+    let node = node.to_string();
+    let endpoint = endpoint.to_string();
+
+    metrics::counter!(
+        "bigchaindb_attempts_total",
+        "node" => node.clone(),
+        "endpoint" => endpoint.clone(),
+        "outcome" => outcome(success)
+    )
+    .increment(1);
+    metrics::histogram!(
+        "bigchaindb_attempt_duration_seconds",
+        "node" => node,
+        "endpoint" => endpoint
+    )
+    .record(latency.as_secs_f64());
+}
+
+#[cfg(not(feature = "metrics"))]
+pub fn record_attempt(_node: &str, _endpoint: &str, _latency: Duration, _success: bool) {}
+
+/// Record the outcome of a request after all its attempts.
+#[cfg(feature = "metrics")]
+pub fn record_request(endpoint: &str, attempts: usize, success: bool) {
+    let endpoint = endpoint.to_string();
+
+    metrics::counter!(
+        "bigchaindb_requests_total",
+        "endpoint" => endpoint.clone(),
+        "outcome" => outcome(success)
+    )
+    .increment(1);
+    metrics::histogram!("bigchaindb_request_attempts", "endpoint" => endpoint)
+        .record(attempts as f64);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub fn record_request(_endpoint: &str, _attempts: usize, _success: bool) {}
@@ -1,13 +1,21 @@
 #![allow(dead_code)]
 
-use std::collections::HashSet;
-
-use crypto_conditions::{self, fulfillment::Fulfillment, Ed25519Sha256};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use crypto_conditions::{
+    self, condition::Condition, fulfillment::Fulfillment, Ed25519Sha256, ThresholdSha256,
+};
+use ed25519_dalek::{Signature, VerifyingKey};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use stable_sort::stable_sorted;
 
-use crate::{cc_jsonify, sha256_hash::sha256_hash, Details, JsonBody};
+use crate::{
+    cc_jsonify,
+    error::Error,
+    keys::{PublicKey, SecretKey},
+    sha256_hash::sha256_hash,
+    Details, JsonBody,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnspentOutput {
@@ -15,18 +23,62 @@ pub struct UnspentOutput {
     pub tx: TransactionTemplate,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl UnspentOutput {
+    pub fn output(&self) -> Option<&Output> {
+        self.tx.outputs.get(self.output_index)
+    }
+}
+
+/// Signature an input still waits for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingSignature {
+    pub input: usize,
+    pub public_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TransactionOutput {
     pub output_index: usize,
     pub transaction_id: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Block {
+    pub height: u64,
+    pub transactions: Vec<TransactionTemplate>,
+}
+
+/// Asset matching a text search, see `Connection::search_assets`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetSearchResult {
+    // id of the `CREATE` transaction of the asset
+    pub id: String,
+    pub data: JsonValue,
+}
+
+/// Metadata matching a text search, see `Connection::search_metadata`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataSearchResult {
+    // id of the transaction holding the metadata
+    pub id: String,
+    pub metadata: JsonValue,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Operation {
     CREATE,
     TRANSFER,
 }
 
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CREATE => "CREATE",
+            Self::TRANSFER => "TRANSFER",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateAsset {
     pub data: JsonValue,
@@ -90,6 +142,17 @@ pub struct Output {
     pub public_keys: Vec<String>,
 }
 
+impl Output {
+    /// Number of signatures spending the output takes: the threshold of its condition, or
+    /// one for each of its public keys.
+    pub fn threshold(&self) -> usize {
+        self.condition
+            .details
+            .threshold
+            .unwrap_or(self.public_keys.len())
+    }
+}
+
 #[stable_sorted]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionTemplate {
@@ -124,6 +187,32 @@ impl TransactionTemplate {
     pub fn serialize_transaction_into_canonical_string(&self) -> String {
         serde_json::to_string(&self).unwrap()
     }
+
+    /// Id of the transaction, the hash of its content without the id.
+    pub fn compute_id(&self) -> String {
+        let mut transaction = self.clone();
+        transaction.id = None;
+        sha256_hash(&transaction.serialize_transaction_into_canonical_string())
+    }
+
+    /// Copy of the transaction without id nor fulfillments, the part covered by signatures.
+    pub fn unsigned(&self) -> Self {
+        let mut transaction = self.clone();
+        transaction.id = None;
+        for input in transaction.inputs.iter_mut() {
+            input.fulfillment = None;
+        }
+        transaction
+    }
+
+    /// Id of the asset held by the transaction: its own id for a `CREATE`, the id of the linked
+    /// `CREATE` for a `TRANSFER`.
+    pub fn asset_id(&self) -> Option<String> {
+        match self.operation.as_ref()? {
+            Operation::CREATE => self.id.clone(),
+            Operation::TRANSFER => self.asset.as_ref()?.get_link_id(),
+        }
+    }
 }
 
 pub struct Transaction;
@@ -167,14 +256,7 @@ impl Transaction {
         metadata: Option<JsonValue>,
     ) -> TransactionTemplate {
         let first_unspent_output = unspent_outputs.first().unwrap();
-        let tx_id = if first_unspent_output.tx.operation.as_ref().unwrap() == &Operation::CREATE {
-            first_unspent_output.to_owned().tx.id.unwrap()
-        } else {
-            let Asset::Link(asset) = first_unspent_output.to_owned().tx.asset.unwrap() else {
-                unreachable!()
-            };
-            asset.id
-        };
+        let tx_id = first_unspent_output.tx.asset_id().unwrap();
 
         let asset = Asset::Link(TransferAsset { id: tx_id });
 
@@ -198,8 +280,9 @@ impl Transaction {
         Self::make_transaction(Operation::TRANSFER, Some(asset), metadata, outputs, inputs)
     }
 
-    pub fn make_ed25519_condition(pubkey: &str, json: bool) -> Option<JsonBody> {
-        let fulfillment = Ed25519Sha256::from(pubkey);
+    pub fn make_ed25519_condition(public_key: &PublicKey, json: bool) -> Option<JsonBody> {
+        let mut fulfillment = Ed25519Sha256::new();
+        fulfillment.set_public_key(*public_key.as_bytes());
 
         // TODO: implement this from js code
         // return json ? ccJsonify(ed25519Fulfillment) : ed25519Fulfillment
@@ -210,87 +293,482 @@ impl Transaction {
         }
     }
 
+    /// Output of `amount` shares locked to the single key `public_key`.
+    pub fn make_ed25519_output(public_key: &PublicKey, amount: u64) -> Output {
+        let condition = Self::make_ed25519_condition(public_key, true)
+            .expect("an ed25519 condition has a JSON form");
+        Self::make_output(condition, amount.to_string())
+    }
+
+    /// Condition met once `threshold` of the keys of `public_keys` have signed: a
+    /// threshold-sha-256 of one ed25519-sha-256 subcondition per key.
+    pub fn make_threshold_condition(
+        public_keys: &[PublicKey],
+        threshold: usize,
+    ) -> Result<JsonBody, Error> {
+        if threshold == 0 || threshold > public_keys.len() {
+            return Err(Error::InvalidThreshold {
+                threshold,
+                keys: public_keys.len(),
+            });
+        }
+
+        let mut condition = ThresholdSha256::new(threshold);
+        let mut subconditions = Vec::new();
+        for public_key in public_keys {
+            condition.add_subcondition(Self::ed25519_condition(public_key));
+            subconditions.push(Details {
+                public_key: public_key.to_string(),
+                subconditions: None,
+                threshold: None,
+                type_: String::from(Ed25519Sha256::TYPE_NAME),
+            });
+        }
+
+        Ok(JsonBody {
+            details: Details {
+                public_key: String::new(),
+                subconditions: Some(subconditions),
+                threshold: Some(threshold),
+                type_: String::from(ThresholdSha256::TYPE_NAME),
+            },
+            uri: condition.get_condition_uri(),
+        })
+    }
+
     pub fn make_output(condition: JsonBody, amount: String) -> Output {
-        let mut public_keys = HashSet::new();
-        let mut get_public_keys = |details: &Details| {
-            if details.type_ == Ed25519Sha256::TYPE_NAME {
-                public_keys.insert(details.public_key.clone());
-            } else {
-                // TODO: implement this from js code
-                // } else if (details.type === 'threshold-sha-256') {
-                //     details.subconditions.map(getPublicKeys)
-                // }
-                todo!()
+        fn get_public_keys(details: &Details, public_keys: &mut Vec<String>) {
+            if details.type_ == ThresholdSha256::TYPE_NAME {
+                for subcondition in details.subconditions.iter().flatten() {
+                    get_public_keys(subcondition, public_keys);
+                }
+            } else if details.type_ == Ed25519Sha256::TYPE_NAME
+                && !public_keys.contains(&details.public_key)
+            {
+                public_keys.push(details.public_key.clone());
             }
-        };
+        }
 
-        get_public_keys(&condition.details);
+        let mut public_keys = Vec::new();
+        get_public_keys(&condition.details, &mut public_keys);
 
         Output {
             condition,
             amount,
-            public_keys: Vec::from_iter(public_keys),
+            public_keys,
         }
     }
 
+    /// Sign every input of `transaction`, the input `i` with `private_keys[i]`. An input with
+    /// several owners only gets the signature of its key and is taken to need every owner, see
+    /// `add_signature` for the others and for outputs with a lower threshold. The id is only
+    /// set once every input is fully signed.
+    ///
+    /// Fails with `Error::MissingSignature` when there is no key for an input, and with
+    /// `Error::InvalidFulfillment` when an input does not reference the output it spends.
     pub fn sign_transaction(
         transaction: &TransactionTemplate,
-        private_keys: Vec<&str>,
-    ) -> TransactionTemplate {
-        let mut signed_transaction: TransactionTemplate = transaction.clone();
-        let mut serialized_transaction = transaction.serialize_transaction_into_canonical_string();
+        private_keys: Vec<&SecretKey>,
+    ) -> Result<TransactionTemplate, Error> {
+        let mut signed_transaction = transaction.unsigned();
 
         for (index, input_template) in signed_transaction.inputs.iter_mut().enumerate() {
-            let private_key = private_keys[index];
-            let private_key = bs58::decode(private_key).into_vec().unwrap();
-
-            let transaction_unique_fulfillment: &str =
-                if let Some(fulfills) = &input_template.fulfills {
-                    let transaction_id = fulfills
-                        .get("transaction_id")
-                        .expect("no transaction_id in json::Value")
-                        .to_string()
-                        .replace('\"', ""); // remove double quotes around the string
-                    let output_index = fulfills
-                        .get("output_index")
-                        .expect("no output_index in json::Value")
-                        .to_string();
-                    serialized_transaction.push_str(&transaction_id);
-                    serialized_transaction.push_str(&output_index);
-                    &serialized_transaction
-                } else {
-                    &serialized_transaction
+            let private_key = private_keys
+                .get(index)
+                .ok_or_else(|| Error::MissingSignature {
+                    input: index,
+                    public_key: input_template
+                        .owners_before
+                        .first()
+                        .cloned()
+                        .unwrap_or_default(),
+                })?;
+            let signature = Self::sign_digest(transaction, index, private_key)?;
+            let threshold = Self::input_threshold(transaction, &[], index);
+            let fulfillment_uri =
+                Self::make_fulfillment_uri(transaction, index, &[signature], threshold)?;
+            input_template.fulfillment = Some(fulfillment_uri);
+        }
+        Self::set_id_if_signed(&mut signed_transaction, &[]);
+
+        Ok(signed_transaction)
+    }
+
+    /// Sign the input `index` with the key of one of its owners, keeping the signatures of the
+    /// other owners and the fulfillments of the other inputs. The id is only set once every
+    /// input is fully signed.
+    ///
+    /// `spent_outputs` are the outputs spent by the inputs, in their order, which tell how many
+    /// owners of an input have to sign. An input whose output is not given, such as the input
+    /// of a CREATE, needs every owner.
+    pub fn add_signature(
+        transaction: &TransactionTemplate,
+        spent_outputs: &[Output],
+        index: usize,
+        private_key: &SecretKey,
+    ) -> Result<TransactionTemplate, Error> {
+        let input = transaction
+            .inputs
+            .get(index)
+            .ok_or(Error::NoSuchInput(index))?;
+        let public_key = private_key.public_key();
+        if !input.owners_before.contains(&public_key.to_string()) {
+            return Err(Error::NotAnOwner {
+                input: index,
+                public_key: public_key.to_string(),
+            });
+        }
+
+        let threshold = Self::input_threshold(transaction, spent_outputs, index);
+        let mut signatures = match &input.fulfillment {
+            Some(fulfillment_uri) if input.owners_before.len() > 1 => {
+                Self::read_signatures(transaction, index, fulfillment_uri, threshold)?
+            }
+            _ => Vec::new(),
+        };
+        signatures.retain(|signature| signature.public_key != Some(*public_key.as_bytes()));
+        signatures.push(Self::sign_digest(transaction, index, private_key)?);
+
+        let mut signed_transaction = transaction.clone();
+        signed_transaction.inputs[index].fulfillment = Some(Self::make_fulfillment_uri(
+            transaction,
+            index,
+            &signatures,
+            threshold,
+        )?);
+        Self::set_id_if_signed(&mut signed_transaction, spent_outputs);
+
+        Ok(signed_transaction)
+    }
+
+    /// Owners whose signature an input still waits for, none once as many owners as the
+    /// threshold of the input have signed. See `add_signature` for `spent_outputs`.
+    pub fn missing_signatures(
+        transaction: &TransactionTemplate,
+        spent_outputs: &[Output],
+    ) -> Vec<PendingSignature> {
+        transaction
+            .inputs
+            .iter()
+            .enumerate()
+            .flat_map(|(index, input)| {
+                let threshold = Self::input_threshold(transaction, spent_outputs, index);
+                let signers = Self::signers(input, threshold);
+                let met = signers.len() >= threshold;
+                input
+                    .owners_before
+                    .iter()
+                    .filter(move |public_key| !met && !signers.contains(public_key))
+                    .map(move |public_key| PendingSignature {
+                        input: index,
+                        public_key: public_key.clone(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Combine copies of a transaction signed by different owners. Every signature is checked
+    /// against its input and its owner, and the signatures of the owners of an input are
+    /// gathered in its threshold fulfillment. See `add_signature` for `spent_outputs`.
+    pub fn combine_signatures(
+        copies: &[TransactionTemplate],
+        spent_outputs: &[Output],
+    ) -> Result<TransactionTemplate, Error> {
+        let (first, _) = copies.split_first().ok_or(Error::TransactionMismatch)?;
+        let mut combined = first.unsigned();
+        let unsigned = combined.serialize_transaction_into_canonical_string();
+
+        let mut signatures: Vec<Vec<Ed25519Sha256>> = vec![Vec::new(); combined.inputs.len()];
+        for copy in copies {
+            if copy
+                .unsigned()
+                .serialize_transaction_into_canonical_string()
+                != unsigned
+            {
+                return Err(Error::TransactionMismatch);
+            }
+
+            for (index, input) in copy.inputs.iter().enumerate() {
+                let Some(fulfillment_uri) = &input.fulfillment else {
+                    continue;
                 };
+                let threshold = Self::input_threshold(&combined, spent_outputs, index);
+                let signatures_read =
+                    Self::read_signatures(&combined, index, fulfillment_uri, threshold)?;
+                for signature in signatures_read {
+                    if !signatures[index]
+                        .iter()
+                        .any(|known| known.public_key == signature.public_key)
+                    {
+                        signatures[index].push(signature);
+                    }
+                }
+            }
+        }
 
-            let transaction_hash = sha256_hash(transaction_unique_fulfillment);
+        for (index, signatures) in signatures.iter().enumerate() {
+            if !signatures.is_empty() {
+                let threshold = Self::input_threshold(&combined, spent_outputs, index);
+                combined.inputs[index].fulfillment = Some(Self::make_fulfillment_uri(
+                    &combined, index, signatures, threshold,
+                )?);
+            }
+        }
+        Self::set_id_if_signed(&mut combined, spent_outputs);
 
-            let mut ed25519_fulfillment = Ed25519Sha256::new();
-            let mut buffer = [0u8; 32];
-            buffer.copy_from_slice(&private_key[..]);
-            let transaction_hash = hex::decode(transaction_hash).unwrap();
-            ed25519_fulfillment.sign(&transaction_hash, &buffer);
+        Ok(combined)
+    }
 
-            let fulfillment_uri = ed25519_fulfillment.serialize_uri();
+    // the id of a transaction covers its fulfillments, so it is left out until all are there
+    fn set_id_if_signed(transaction: &mut TransactionTemplate, spent_outputs: &[Output]) {
+        transaction.id = None;
+        if Self::missing_signatures(transaction, spent_outputs).is_empty() {
+            transaction.id = Some(transaction.compute_id());
+        }
+    }
 
-            input_template.fulfillment = Some(fulfillment_uri);
+    /// Hash signed by the owner of the input `index`: the unsigned transaction followed by the
+    /// output the input spends, so that a signature is only valid for one input.
+    pub fn input_digest(transaction: &TransactionTemplate, index: usize) -> Result<Vec<u8>, Error> {
+        let input = transaction
+            .inputs
+            .get(index)
+            .ok_or(Error::NoSuchInput(index))?;
+        let mut message = transaction
+            .unsigned()
+            .serialize_transaction_into_canonical_string();
+
+        if let Some(fulfills) = &input.fulfills {
+            let transaction_id = fulfills
+                .get("transaction_id")
+                .and_then(JsonValue::as_str)
+                .ok_or(Error::InvalidFulfillment(index))?;
+            let output_index = fulfills
+                .get("output_index")
+                .and_then(JsonValue::as_u64)
+                .ok_or(Error::InvalidFulfillment(index))?;
+            message.push_str(transaction_id);
+            message.push_str(&output_index.to_string());
+        }
+
+        Ok(hex::decode(sha256_hash(&message)).unwrap())
+    }
+
+    /// Ed25519 fulfillment URI of the input `index` signed with `private_key`. It fulfills an
+    /// input with a single owner, see `fulfill_input` for the inputs with several.
+    pub fn sign_input(
+        transaction: &TransactionTemplate,
+        index: usize,
+        private_key: &SecretKey,
+    ) -> Result<String, Error> {
+        Ok(Self::sign_digest(transaction, index, private_key)?.serialize_uri())
+    }
+
+    /// Fulfillment of the input `index` from `sign_input` signatures of its owners: the
+    /// signature itself for a single owner, otherwise a threshold fulfillment in which the
+    /// owners yet to sign are left as subconditions. See `add_signature` for `spent_outputs`.
+    pub fn fulfill_input(
+        transaction: &TransactionTemplate,
+        spent_outputs: &[Output],
+        index: usize,
+        signatures: &[String],
+    ) -> Result<String, Error> {
+        let signatures = signatures
+            .iter()
+            .map(|signature| {
+                decode_fulfillment(signature)
+                    .and_then(|bytes| Ed25519Sha256::parse_binary(&bytes))
+                    .ok_or(Error::InvalidFulfillment(index))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let threshold = Self::input_threshold(transaction, spent_outputs, index);
+        Self::make_fulfillment_uri(transaction, index, &signatures, threshold)
+    }
+
+    /// Check that `fulfillment_uri` holds a valid ed25519 signature of the input `index` and
+    /// return the public key that made it.
+    pub fn verify_input(
+        transaction: &TransactionTemplate,
+        index: usize,
+        fulfillment_uri: &str,
+    ) -> Result<PublicKey, Error> {
+        if index >= transaction.inputs.len() {
+            return Err(Error::InvalidFulfillment(index));
         }
+        let digest = Self::input_digest(transaction, index)?;
+
+        let signature = decode_fulfillment(fulfillment_uri)
+            .and_then(|bytes| Ed25519Sha256::parse_binary(&bytes))
+            .ok_or(Error::InvalidFulfillment(index))?;
+        verify_signature(&signature, &digest, index)
+    }
+
+    fn ed25519_condition(public_key: &PublicKey) -> Condition {
+        let mut fulfillment = Ed25519Sha256::new();
+        fulfillment.set_public_key(*public_key.as_bytes());
+        fulfillment.get_condition()
+    }
 
-        let serialized_signed_transaction =
-            signed_transaction.serialize_transaction_into_canonical_string();
-        signed_transaction.id = Some(sha256_hash(&serialized_signed_transaction));
+    fn sign_digest(
+        transaction: &TransactionTemplate,
+        index: usize,
+        private_key: &SecretKey,
+    ) -> Result<Ed25519Sha256, Error> {
+        let mut ed25519_fulfillment = Ed25519Sha256::new();
+        ed25519_fulfillment.sign(
+            &Self::input_digest(transaction, index)?,
+            private_key.as_bytes(),
+        );
 
-        signed_transaction
+        Ok(ed25519_fulfillment)
+    }
+
+    // signatures the input `index` takes, from the output it spends if given
+    fn input_threshold(
+        transaction: &TransactionTemplate,
+        spent_outputs: &[Output],
+        index: usize,
+    ) -> usize {
+        match spent_outputs.get(index) {
+            Some(output) => output.threshold(),
+            None => transaction
+                .inputs
+                .get(index)
+                .map_or(1, |input| input.owners_before.len()),
+        }
+    }
+
+    fn make_fulfillment_uri(
+        transaction: &TransactionTemplate,
+        index: usize,
+        signatures: &[Ed25519Sha256],
+        threshold: usize,
+    ) -> Result<String, Error> {
+        let owners = &transaction
+            .inputs
+            .get(index)
+            .ok_or(Error::NoSuchInput(index))?
+            .owners_before;
+        if let ([] | [_], [signature]) = (owners.as_slice(), signatures) {
+            return Ok(signature.serialize_uri());
+        }
+
+        let mut fulfillment = ThresholdSha256::new(threshold);
+        for owner in owners {
+            let owner: PublicKey = owner.parse()?;
+            match signatures
+                .iter()
+                .find(|signature| signature.public_key == Some(*owner.as_bytes()))
+            {
+                Some(signature) => fulfillment.add_subfulfillment(signature.clone()),
+                None => fulfillment.add_subcondition(Self::ed25519_condition(&owner)),
+            }
+        }
+
+        Ok(fulfillment.serialize_uri())
+    }
+
+    // signatures held by the fulfillment of the input `index`, each checked against the input
+    // and its owners
+    fn read_signatures(
+        transaction: &TransactionTemplate,
+        index: usize,
+        fulfillment_uri: &str,
+        threshold: usize,
+    ) -> Result<Vec<Ed25519Sha256>, Error> {
+        let invalid = || Error::InvalidFulfillment(index);
+        let digest = Self::input_digest(transaction, index)?;
+        let owners = &transaction.inputs[index].owners_before;
+        let bytes = decode_fulfillment(fulfillment_uri).ok_or_else(invalid)?;
+
+        let signatures = if owners.len() < 2 {
+            vec![Ed25519Sha256::parse_binary(&bytes).ok_or_else(invalid)?]
+        } else {
+            let threshold = ThresholdSha256::parse_binary(&bytes, threshold).ok_or_else(invalid)?;
+            // each owner appears once, as a signature or as the condition still to sign
+            let mut conditions = owners
+                .iter()
+                .map(|owner| Ok(Self::ed25519_condition(&owner.parse()?)))
+                .collect::<Result<Vec<_>, Error>>()?;
+            for subcondition in threshold.subconditions.iter() {
+                let position = conditions
+                    .iter()
+                    .position(|condition| *condition == subcondition.condition())
+                    .ok_or_else(invalid)?;
+                conditions.swap_remove(position);
+            }
+            if !conditions.is_empty() {
+                return Err(invalid());
+            }
+            threshold.subfulfillments().cloned().collect()
+        };
+
+        for signature in signatures.iter() {
+            let public_key = verify_signature(signature, &digest, index)?;
+            if !owners.contains(&public_key.to_string()) {
+                return Err(Error::NotAnOwner {
+                    input: index,
+                    public_key: public_key.to_string(),
+                });
+            }
+        }
+
+        Ok(signatures)
+    }
+
+    // owners whose signature the fulfillment of `input` holds, without checking the signatures
+    fn signers(input: &InputTemplate, threshold: usize) -> Vec<String> {
+        let Some(fulfillment_uri) = &input.fulfillment else {
+            return Vec::new();
+        };
+        if input.owners_before.len() < 2 {
+            return input.owners_before.clone();
+        }
+
+        decode_fulfillment(fulfillment_uri)
+            .and_then(|bytes| ThresholdSha256::parse_binary(&bytes, threshold))
+            .map(|threshold| {
+                threshold
+                    .subfulfillments()
+                    .filter_map(|signature| PublicKey::from_bytes(&signature.public_key?).ok())
+                    .map(|public_key| public_key.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 }
 
+fn decode_fulfillment(fulfillment_uri: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(fulfillment_uri.trim_end_matches('='))
+        .ok()
+}
+
+// check that `signature` signs `digest` and return its public key
+fn verify_signature(
+    signature: &Ed25519Sha256,
+    digest: &[u8],
+    index: usize,
+) -> Result<PublicKey, Error> {
+    let invalid = || Error::InvalidFulfillment(index);
+    let (Some(public_key), Some(signature)) = (signature.public_key, signature.signature) else {
+        return Err(invalid());
+    };
+
+    VerifyingKey::from_bytes(&public_key)
+        .and_then(|key| key.verify_strict(digest, &Signature::from_bytes(&signature)))
+        .map_err(|_| invalid())?;
+    PublicKey::from_bytes(&public_key)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_make_ed25519_condition_with_cc_jsonify() {
-        let bytes = [1u8; 32];
-        let pk = bs58::encode(bytes).into_string();
+        let pk = PublicKey::from_bytes(&[1u8; 32]).unwrap();
 
         let condition = Transaction::make_ed25519_condition(&pk, true).unwrap();
 
@@ -304,8 +782,7 @@ mod tests {
 
     #[test]
     fn test_make_output() {
-        let bytes = [1u8; 32];
-        let pk = bs58::encode(bytes).into_string();
+        let pk = PublicKey::from_bytes(&[1u8; 32]).unwrap();
 
         let condition = Transaction::make_ed25519_condition(&pk, true).unwrap();
 
@@ -336,14 +813,17 @@ mod tests {
         let metadata = serde_json::json!({"metadata": "metadata"});
         let asset = Some(assetdata);
 
-        let bytes = [1u8; 32];
-        let pk = bs58::encode(bytes).into_string();
+        let pk = PublicKey::from_bytes(&[1u8; 32]).unwrap();
         let condition = Transaction::make_ed25519_condition(&pk, true).unwrap();
 
         let output = Transaction::make_output(condition, String::from("1"));
 
-        let transaction =
-            Transaction::make_create_transaction(asset, Some(metadata), vec![output], vec![pk]);
+        let transaction = Transaction::make_create_transaction(
+            asset,
+            Some(metadata),
+            vec![output],
+            vec![pk.to_string()],
+        );
         let json = transaction.serialize_transaction_into_canonical_string();
 
         let json_target = r#"{"asset":{"data":{"ft":{"device":"device","signature":"signature"}}},"id":null,"inputs":[{"fulfillment":null,"fulfills":null,"owners_before":["4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi"]}],"metadata":{"metadata":"metadata"},"operation":"CREATE","outputs":[{"amount":"1","condition":{"details":{"public_key":"4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi","type":"ed25519-sha-256"},"uri":"ni:///sha-256;SSSZwcfcc76xHGoY48JsUThq0cr6fgJWCR8lXx9e5F0?fpt=ed25519-sha-256&cost=131072"},"public_keys":["4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi"]}],"version":"2.0"}"#;
@@ -361,8 +841,12 @@ mod tests {
         let metadata = serde_json::json!({"metadata": "metadata"});
         let asset = Some(assetdata);
 
-        let public_key = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX";
-        let private_key = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT";
+        let public_key: PublicKey = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX"
+            .parse()
+            .unwrap();
+        let private_key: SecretKey = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT"
+            .parse()
+            .unwrap();
         let condition = Transaction::make_ed25519_condition(&public_key, true).unwrap();
 
         let output = Transaction::make_output(condition, String::from("1"));
@@ -374,9 +858,9 @@ mod tests {
             vec![public_key.to_string()],
         );
 
-        let private_keys = vec![private_key];
+        let private_keys = vec![&private_key];
 
-        let signed_transaction = Transaction::sign_transaction(&transaction, private_keys);
+        let signed_transaction = Transaction::sign_transaction(&transaction, private_keys).unwrap();
 
         assert_eq!(
             signed_transaction.id.unwrap(),
@@ -394,8 +878,12 @@ mod tests {
         });
         let metadata = serde_json::json!({"metadata": "metadata"});
         let asset = Some(assetdata);
-        let public_key = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX";
-        let private_key = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT";
+        let public_key: PublicKey = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX"
+            .parse()
+            .unwrap();
+        let private_key: SecretKey = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT"
+            .parse()
+            .unwrap();
         let condition = Transaction::make_ed25519_condition(&public_key, true).unwrap();
         let output = Transaction::make_output(condition, String::from("1"));
         let transaction = Transaction::make_create_transaction(
@@ -404,9 +892,9 @@ mod tests {
             vec![output],
             vec![public_key.to_string()],
         );
-        let private_keys = vec![private_key];
+        let private_keys = vec![&private_key];
         let signed_create_transaction =
-            Transaction::sign_transaction(&transaction, private_keys.clone());
+            Transaction::sign_transaction(&transaction, private_keys.clone()).unwrap();
 
         let condition = Transaction::make_ed25519_condition(&public_key, true).unwrap();
         let output = Transaction::make_output(condition, String::from("1"));
@@ -419,11 +907,407 @@ mod tests {
             Some(metadata),
         );
         let signed_transfer_transaction =
-            Transaction::sign_transaction(&transfer_transaction, private_keys);
+            Transaction::sign_transaction(&transfer_transaction, private_keys).unwrap();
 
         assert_eq!(
             signed_transfer_transaction.id.unwrap(),
             "dc084d12f8f05e8eec77dab8023087eecfd6afd9ddb573c6243d3cfe3416f50f"
         );
     }
+
+    #[test]
+    fn test_asset_id() {
+        let public_key: PublicKey = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX"
+            .parse()
+            .unwrap();
+        let private_key: SecretKey = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT"
+            .parse()
+            .unwrap();
+        let condition = Transaction::make_ed25519_condition(&public_key, true).unwrap();
+        let output = Transaction::make_output(condition, String::from("1"));
+        let create_transaction = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                Some(serde_json::json!({ "name": "asset" })),
+                None,
+                vec![output.clone()],
+                vec![public_key.to_string()],
+            ),
+            vec![&private_key],
+        )
+        .unwrap();
+        // a CREATE defines the asset under its own id
+        assert_eq!(create_transaction.asset_id(), create_transaction.id);
+
+        let transfer_transaction = Transaction::sign_transaction(
+            &Transaction::make_transfer_transaction(
+                vec![UnspentOutput {
+                    tx: create_transaction.clone(),
+                    output_index: 0,
+                }],
+                vec![output.clone()],
+                None,
+            ),
+            vec![&private_key],
+        )
+        .unwrap();
+        assert_eq!(transfer_transaction.asset_id(), create_transaction.id);
+
+        // and a TRANSFER of a TRANSFER still links to the CREATE
+        let second_transfer = Transaction::make_transfer_transaction(
+            vec![UnspentOutput {
+                tx: transfer_transaction,
+                output_index: 0,
+            }],
+            vec![output],
+            None,
+        );
+        assert_eq!(second_transfer.asset_id(), create_transaction.id);
+
+        assert_eq!(TransactionTemplate::default().asset_id(), None);
+    }
+
+    #[test]
+    fn test_sign_transfer_of_several_inputs() {
+        let public_key: PublicKey = "6zaQbbRi7RCFhCF35tpVDu2nEfR9fZCqx2MvUa7pKRmX"
+            .parse()
+            .unwrap();
+        let private_key: SecretKey = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT"
+            .parse()
+            .unwrap();
+        let condition = Transaction::make_ed25519_condition(&public_key, true).unwrap();
+        let output = Transaction::make_output(condition, String::from("1"));
+        let create_transaction = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                None,
+                None,
+                vec![output.clone(), output.clone()],
+                vec![public_key.to_string()],
+            ),
+            vec![&private_key],
+        )
+        .unwrap();
+
+        let unspent_outputs = (0..2)
+            .map(|output_index| UnspentOutput {
+                output_index,
+                tx: create_transaction.clone(),
+            })
+            .collect();
+        let transfer_transaction =
+            Transaction::make_transfer_transaction(unspent_outputs, vec![output], None);
+        let signed =
+            Transaction::sign_transaction(&transfer_transaction, vec![&private_key, &private_key])
+                .unwrap();
+
+        // each input signs the unsigned transaction and its own output only
+        for (index, input) in signed.inputs.iter().enumerate() {
+            let fulfillment = input.fulfillment.as_deref().unwrap();
+            assert_eq!(
+                Transaction::verify_input(&signed, index, fulfillment).unwrap(),
+                public_key
+            );
+        }
+        let first = signed.inputs[0].fulfillment.clone().unwrap();
+        assert!(matches!(
+            Transaction::verify_input(&signed, 1, &first),
+            Err(Error::InvalidFulfillment(1))
+        ));
+        assert_eq!(signed.id, Some(signed.compute_id()));
+
+        // a malformed reference to the spent output is an error, not a panic
+        let mut malformed = signed.clone();
+        malformed.inputs[1].fulfills = Some(serde_json::json!({}));
+        assert!(matches!(
+            Transaction::input_digest(&malformed, 1),
+            Err(Error::InvalidFulfillment(1))
+        ));
+        assert!(Transaction::combine_signatures(&[malformed], &[]).is_err());
+        assert!(matches!(
+            Transaction::input_digest(&signed, 2),
+            Err(Error::NoSuchInput(2))
+        ));
+    }
+
+    #[test]
+    fn test_sign_transaction_errors() {
+        let private_key: SecretKey = "CHwxsNPzRXTzCz25KZ9TJcBJ45H25JKkLL4HrX1nBfXT"
+            .parse()
+            .unwrap();
+        let public_key = private_key.public_key();
+        let output = Transaction::make_ed25519_output(&public_key, 1);
+        let create_transaction = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                None,
+                None,
+                vec![output.clone(), output.clone()],
+                vec![public_key.to_string()],
+            ),
+            vec![&private_key],
+        )
+        .unwrap();
+
+        let unspent_outputs = (0..2)
+            .map(|output_index| UnspentOutput {
+                output_index,
+                tx: create_transaction.clone(),
+            })
+            .collect();
+        let mut transfer_transaction =
+            Transaction::make_transfer_transaction(unspent_outputs, vec![output], None);
+
+        // a key for the first input only
+        assert!(matches!(
+            Transaction::sign_transaction(&transfer_transaction, vec![&private_key]),
+            Err(Error::MissingSignature { input: 1, public_key: owner })
+                if owner == public_key.to_string()
+        ));
+
+        transfer_transaction.inputs[1].fulfills = Some(serde_json::json!({ "output_index": 1 }));
+        assert!(matches!(
+            Transaction::sign_transaction(&transfer_transaction, vec![&private_key, &private_key]),
+            Err(Error::InvalidFulfillment(1))
+        ));
+    }
+
+    #[test]
+    fn test_partial_signatures() {
+        let first_key = SecretKey::generate();
+        let second_key = SecretKey::generate();
+        let outputs: Vec<Output> = [&first_key, &second_key]
+            .iter()
+            .map(|key| {
+                let condition =
+                    Transaction::make_ed25519_condition(&key.public_key(), true).unwrap();
+                Transaction::make_output(condition, String::from("1"))
+            })
+            .collect();
+        let create_transaction = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                None,
+                None,
+                outputs.clone(),
+                vec![first_key.public_key().to_string()],
+            ),
+            vec![&first_key],
+        )
+        .unwrap();
+
+        let unspent_outputs = (0..2)
+            .map(|output_index| UnspentOutput {
+                output_index,
+                tx: create_transaction.clone(),
+            })
+            .collect();
+        let transfer_transaction =
+            Transaction::make_transfer_transaction(unspent_outputs, outputs, None);
+        assert_eq!(
+            Transaction::missing_signatures(&transfer_transaction, &[]).len(),
+            2
+        );
+
+        // each owner signs its own input on its own copy
+        let first = Transaction::add_signature(&transfer_transaction, &[], 0, &first_key).unwrap();
+        let second =
+            Transaction::add_signature(&transfer_transaction, &[], 1, &second_key).unwrap();
+        assert_eq!(
+            Transaction::missing_signatures(&first, &[]),
+            vec![PendingSignature {
+                input: 1,
+                public_key: second_key.public_key().to_string(),
+            }]
+        );
+        assert!(first.id.is_none());
+        assert!(matches!(
+            Transaction::add_signature(&transfer_transaction, &[], 1, &first_key),
+            Err(Error::NotAnOwner { input: 1, .. })
+        ));
+
+        let combined = Transaction::combine_signatures(&[first.clone(), second], &[]).unwrap();
+        let expected =
+            Transaction::sign_transaction(&transfer_transaction, vec![&first_key, &second_key])
+                .unwrap();
+        assert!(Transaction::missing_signatures(&combined, &[]).is_empty());
+        assert_eq!(combined.id, expected.id);
+
+        // a fulfillment moved to another input is rejected
+        let mut forged = first.clone();
+        forged.inputs[1].fulfillment = forged.inputs[0].fulfillment.clone();
+        assert!(matches!(
+            Transaction::combine_signatures(&[forged], &[]),
+            Err(Error::InvalidFulfillment(1))
+        ));
+        assert!(matches!(
+            Transaction::combine_signatures(&[first, create_transaction], &[]),
+            Err(Error::TransactionMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_threshold_signatures() {
+        let first_key = SecretKey::generate();
+        let second_key = SecretKey::generate();
+        let owners = [first_key.public_key(), second_key.public_key()];
+        let condition = Transaction::make_threshold_condition(&owners, 2).unwrap();
+        assert!(condition
+            .uri
+            .ends_with("?fpt=threshold-sha-256&cost=264192&subtypes=ed25519-sha-256"));
+        let output = Transaction::make_output(condition, String::from("1"));
+        assert_eq!(
+            output.public_keys,
+            owners.iter().map(ToString::to_string).collect::<Vec<_>>()
+        );
+
+        let create_transaction = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                None,
+                None,
+                vec![output.clone()],
+                vec![first_key.public_key().to_string()],
+            ),
+            vec![&first_key],
+        )
+        .unwrap();
+        let transfer_transaction = Transaction::make_transfer_transaction(
+            vec![UnspentOutput {
+                output_index: 0,
+                tx: create_transaction,
+            }],
+            vec![output.clone()],
+            None,
+        );
+        let spent_outputs = [output.clone()];
+        assert_eq!(
+            Transaction::missing_signatures(&transfer_transaction, &spent_outputs).len(),
+            2
+        );
+
+        // both owners sign the single input, each on its own copy
+        let first =
+            Transaction::add_signature(&transfer_transaction, &spent_outputs, 0, &first_key)
+                .unwrap();
+        let second =
+            Transaction::add_signature(&transfer_transaction, &spent_outputs, 0, &second_key)
+                .unwrap();
+        assert_eq!(
+            Transaction::missing_signatures(&first, &spent_outputs),
+            vec![PendingSignature {
+                input: 0,
+                public_key: second_key.public_key().to_string(),
+            }]
+        );
+        assert!(first.id.is_none());
+
+        let combined =
+            Transaction::combine_signatures(&[first.clone(), second], &spent_outputs).unwrap();
+        assert!(Transaction::missing_signatures(&combined, &spent_outputs).is_empty());
+        assert_eq!(combined.id, Some(combined.compute_id()));
+        let in_turn = Transaction::add_signature(&first, &spent_outputs, 0, &second_key).unwrap();
+        assert_eq!(
+            in_turn.inputs[0].fulfillment,
+            combined.inputs[0].fulfillment
+        );
+        assert_eq!(in_turn.id, combined.id);
+
+        // the fulfillment meets the condition of the spent output
+        let fulfillment = decode_fulfillment(combined.inputs[0].fulfillment.as_deref().unwrap())
+            .and_then(|bytes| ThresholdSha256::parse_binary(&bytes, 2))
+            .unwrap();
+        assert_eq!(fulfillment.threshold, 2);
+        assert_eq!(fulfillment.get_condition_uri(), output.condition.uri);
+
+        assert!(matches!(
+            Transaction::add_signature(
+                &transfer_transaction,
+                &spent_outputs,
+                0,
+                &SecretKey::generate()
+            ),
+            Err(Error::NotAnOwner { input: 0, .. })
+        ));
+        // a bare signature does not fulfill an input with several owners
+        let mut forged = transfer_transaction.clone();
+        forged.inputs[0].fulfillment =
+            Some(Transaction::sign_input(&transfer_transaction, 0, &first_key).unwrap());
+        assert!(matches!(
+            Transaction::combine_signatures(&[forged], &spent_outputs),
+            Err(Error::InvalidFulfillment(0))
+        ));
+    }
+
+    #[test]
+    fn test_m_of_n_signatures() {
+        let keys: Vec<SecretKey> = (0..3).map(|_| SecretKey::generate()).collect();
+        let owners: Vec<PublicKey> = keys.iter().map(SecretKey::public_key).collect();
+        assert!(matches!(
+            Transaction::make_threshold_condition(&owners, 4),
+            Err(Error::InvalidThreshold {
+                threshold: 4,
+                keys: 3
+            })
+        ));
+        assert!(Transaction::make_threshold_condition(&owners, 0).is_err());
+        let output = Transaction::make_output(
+            Transaction::make_threshold_condition(&owners, 2).unwrap(),
+            String::from("1"),
+        );
+        assert_eq!(output.threshold(), 2);
+
+        let create_transaction = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                None,
+                None,
+                vec![output.clone()],
+                vec![owners[0].to_string()],
+            ),
+            vec![&keys[0]],
+        )
+        .unwrap();
+        let transfer_transaction = Transaction::make_transfer_transaction(
+            vec![UnspentOutput {
+                output_index: 0,
+                tx: create_transaction,
+            }],
+            vec![Transaction::make_ed25519_output(&owners[0], 1)],
+            None,
+        );
+        let spent_outputs = [output.clone()];
+        assert_eq!(
+            Transaction::missing_signatures(&transfer_transaction, &spent_outputs).len(),
+            3
+        );
+
+        // two of the three owners are enough
+        let first =
+            Transaction::add_signature(&transfer_transaction, &spent_outputs, 0, &keys[0]).unwrap();
+        assert_eq!(
+            Transaction::missing_signatures(&first, &spent_outputs).len(),
+            2
+        );
+        assert!(first.id.is_none());
+        let signed = Transaction::add_signature(&first, &spent_outputs, 0, &keys[2]).unwrap();
+        assert!(Transaction::missing_signatures(&signed, &spent_outputs).is_empty());
+        assert_eq!(signed.id, Some(signed.compute_id()));
+
+        // without the spent output, every owner is still expected
+        assert_eq!(
+            Transaction::missing_signatures(&signed, &[]),
+            vec![PendingSignature {
+                input: 0,
+                public_key: owners[1].to_string(),
+            }]
+        );
+
+        let third =
+            Transaction::add_signature(&transfer_transaction, &spent_outputs, 0, &keys[2]).unwrap();
+        let combined = Transaction::combine_signatures(&[first, third], &spent_outputs).unwrap();
+        assert_eq!(combined.inputs[0].fulfillment, signed.inputs[0].fulfillment);
+        assert_eq!(combined.id, signed.id);
+
+        // the fulfillment meets the 2-of-3 condition of the spent output
+        let fulfillment = decode_fulfillment(signed.inputs[0].fulfillment.as_deref().unwrap())
+            .and_then(|bytes| ThresholdSha256::parse_binary(&bytes, 2))
+            .unwrap();
+        assert_eq!(fulfillment.subfulfillments().count(), 2);
+        assert_eq!(fulfillment.get_condition_uri(), output.condition.uri);
+    }
 }
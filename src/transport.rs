@@ -1,22 +1,49 @@
-use std::time::{Duration, Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use futures::future;
+use reqwest::Client;
 use serde::de::DeserializeOwned;
 
 use crate::{
+    client::ClientConfig,
     error::Error,
+    health::NodeHealth,
     request::{NormalizedNode, Request, RequestOption},
+    retry::RetryPolicy,
+    selection::{NodeSelector, Sticky},
+    telemetry,
 };
 
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Clone)]
 pub struct Transport<'a> {
     pub connection_pool: Vec<Request<'a>>,
-    pub timeout: Option<Duration>,
-    // the maximum backoff time is 10 seconds
-    pub max_backoff_time: Duration,
+    // shared by every node so that connections are pooled and reused
+    pub client: Client,
+    pub retry_policy: RetryPolicy,
+    pub selector: Arc<dyn NodeSelector>,
 }
 
 impl<'a> Transport<'a> {
-    pub fn new(nodes: Vec<NormalizedNode<'a>>, timeout: Duration) -> Self {
+    /// Transport sharing a client built from the default `ClientConfig`.
+    ///
+    /// Panics if the client cannot be built, as `reqwest::Client::new` does.
+    pub fn new(nodes: Vec<NormalizedNode<'a>>, retry_policy: RetryPolicy) -> Self {
+        let client = ClientConfig::default()
+            .build()
+            .expect("the default client configuration is valid");
+        Self::with_client(nodes, retry_policy, client)
+    }
+
+    pub fn with_client(
+        nodes: Vec<NormalizedNode<'a>>,
+        retry_policy: RetryPolicy,
+        client: Client,
+    ) -> Self {
         let mut connection_pool = Vec::new();
 
         for node in nodes {
@@ -25,41 +52,136 @@ impl<'a> Transport<'a> {
 
         Self {
             connection_pool,
-            timeout: Some(timeout),
-            max_backoff_time: timeout / 2,
+            client,
+            retry_policy,
+            selector: Arc::new(Sticky::default()),
         }
     }
 
+    /// Send the request to the nodes of the pool until one of them answers, following the
+    /// retry policy of `options` or, if it has none, of the transport.
+    #[tracing::instrument(
+        name = "bigchaindb.request",
+        skip_all,
+        fields(path = path, method = ?options.method, attempts = tracing::field::Empty)
+    )]
     pub async fn forward_request<T: DeserializeOwned>(
-        &mut self,
+        &self,
         path: &str,
         options: &RequestOption<'_>,
     ) -> Result<T, Error> {
-        while self.timeout.is_some() {
-            let start_time = Instant::now();
+        let retry_policy = options.retry_policy.as_ref().unwrap_or(&self.retry_policy);
+        let deadline = Instant::now() + retry_policy.timeout;
+        let mut attempts = 0;
+
+        let result = loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                break Err(Error::RequestTimeout);
+            }
 
             let connection = self.pick_connection();
-            let response: T = connection
-                .request(path, options, self.timeout.unwrap(), self.max_backoff_time)
-                .await?;
-
-            let elapsed = Instant::now().duration_since(start_time);
-
-            if connection.backoff_time.lock().unwrap().is_some()
-                && self.timeout.unwrap().as_millis() > 0
-            {
-                self.timeout = self.timeout.unwrap().checked_sub(elapsed);
-                todo!()
-            } else {
-                // No connection error, the response is valid
-                return Ok(response);
+            // Every node backs off past the deadline: give up without sending anything, so
+            // no failure is held against the node.
+            if connection.get_backoff_time_delta() > timeout {
+                break Err(Error::RequestTimeout);
             }
+
+            let result = connection
+                .request(&self.client, path, options, timeout)
+                .await;
+            attempts += 1;
+
+            match result {
+                Ok(response) => {
+                    connection.record_success();
+                    break Ok(response);
+                }
+                // The transaction id is derived from its content, so a transaction reported
+                // as existing by a retry may have been posted by a previous attempt.
+                Err(Error::DuplicateTransaction { .. }) if attempts > 1 => {
+                    connection.record_success();
+                    break Err(Error::DuplicateTransaction { retried: true });
+                }
+                Err(err) if retry_policy.is_retryable(&err) => {
+                    connection.record_failure(retry_policy);
+                    if let Error::RateLimited { retry_after } = &err {
+                        connection.record_rate_limited(*retry_after);
+                    }
+                    if attempts >= retry_policy.max_attempts {
+                        break Err(err);
+                    }
+                    tracing::info!(
+                        node = connection.node.endpoint,
+                        error = %err,
+                        "retrying request"
+                    );
+                }
+                Err(err) => break Err(err),
+            }
+        };
+
+        tracing::Span::current().record("attempts", attempts);
+        if let Err(err) = &result {
+            tracing::warn!(error = %err, attempts, "request failed");
         }
+        telemetry::record_request(path, attempts, result.is_ok());
+
+        result
+    }
+
+    /// Probe the API root of every node, updating their health and latency.
+    pub async fn check_health(&self) {
+        let probes = self
+            .connection_pool
+            .iter()
+            .map(|connection| connection.probe(&self.client, PROBE_TIMEOUT));
+        future::join_all(probes).await;
+    }
+
+    pub fn node_health(&self) -> Vec<NodeHealth> {
+        let now = Instant::now();
 
-        Err(Error::RequestTimeout)
+        self.connection_pool
+            .iter()
+            .map(|connection| {
+                let stats = connection.stats.lock().unwrap().clone();
+                let backoff_time = *connection.backoff_time.lock().unwrap();
+
+                NodeHealth {
+                    endpoint: connection.node.endpoint.to_string(),
+                    priority: connection.node.priority,
+                    healthy: stats.healthy,
+                    latency: stats.latency,
+                    error_rate: stats.error_rate,
+                    requests: stats.requests,
+                    failures: stats.failures,
+                    backoff: backoff_time
+                        .map(|time| time.saturating_duration_since(now))
+                        .filter(|backoff| !backoff.is_zero()),
+                }
+            })
+            .collect()
     }
 
     fn pick_connection(&self) -> &Request {
+        let now = Instant::now();
+        let available: Vec<&Request> = self
+            .connection_pool
+            .iter()
+            .filter(|connection| connection.is_available(now))
+            .collect();
+
+        if let Some(priority) = available.iter().map(|conn| conn.node.priority).min() {
+            let candidates: Vec<&Request> = available
+                .into_iter()
+                .filter(|conn| conn.node.priority == priority)
+                .collect();
+            let index = self.selector.select(&candidates);
+            return candidates[index.min(candidates.len() - 1)];
+        }
+
+        // Every node is unhealthy or backing off, use the one that will be available first
         let mut connection = &self.connection_pool[0];
         for conn in self.connection_pool.iter() {
             let conn_backoff_time = *conn.backoff_time.lock().unwrap();
@@ -71,3 +193,202 @@ impl<'a> Transport<'a> {
         connection
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        connection::Connection,
+        mock::{fixtures::create_transaction, Fault, MockNode},
+        rate_limit::RateLimit,
+    };
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy::new()
+            .base_delay(Duration::from_millis(10))
+            .jitter(0.0)
+            .retryable_statuses(vec![500])
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_faults() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]).with_retry_policy(retry_policy());
+
+        node.inject_fault(Fault::InternalServerError);
+        node.inject_fault(Fault::DropConnection);
+        assert!(conn.list_outputs("key", None).await.unwrap().is_empty());
+
+        node.inject_fault(Fault::InternalServerError);
+        node.inject_fault(Fault::InternalServerError);
+        let options = RequestOption::new().retry_policy(retry_policy().max_attempts(2));
+        let resp: Result<Vec<u64>, Error> = conn.request("blocks", &options).await;
+        assert!(matches!(
+            resp,
+            Err(Error::ResponseError { status: 500, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_default_client_sends_user_agent() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/api/v1/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut buffer = [0; 4096];
+            let read = socket.read(&mut buffer).unwrap();
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                      Content-Length: 2\r\nConnection: close\r\n\r\n[]",
+                )
+                .unwrap();
+            String::from_utf8_lossy(&buffer[..read]).to_lowercase()
+        });
+
+        let conn = Connection::new(vec![endpoint.as_str()]);
+        assert!(conn.list_outputs("key", None).await.unwrap().is_empty());
+        let request = server.join().unwrap();
+        assert!(request.contains(&format!(
+            "user-agent: bigchaindb-rs/{}",
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_next_node() {
+        // bind then drop a listener to get an address nothing listens on
+        let dead = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let dead_endpoint = format!("http://{}/api/v1/", dead.local_addr().unwrap());
+        drop(dead);
+
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![dead_endpoint.as_str(), endpoint.as_str()])
+            .with_retry_policy(retry_policy());
+
+        assert!(conn.list_outputs("key", None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retried_post_of_existing_transaction_succeeds() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]).with_retry_policy(retry_policy());
+
+        let transaction = create_transaction(0);
+
+        // the transaction reached the node but the answer was lost
+        node.commit(transaction.clone()).unwrap();
+        node.inject_fault(Fault::DropConnection);
+        let posted = conn
+            .post_transaction_commit(transaction.clone())
+            .await
+            .unwrap();
+        assert_eq!(posted.id, transaction.id);
+
+        // posting it again without a retry is still an error
+        assert!(matches!(
+            conn.post_transaction_commit(transaction).await,
+            Err(Error::DuplicateTransaction { retried: false })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_node_is_used_only_when_primary_is_unavailable() {
+        let primary = MockNode::start().await.unwrap();
+        let primary_endpoint = primary.endpoint();
+        let fallback = MockNode::start().await.unwrap();
+        let fallback_endpoint = fallback.endpoint();
+        let conn = Connection::from_nodes(vec![
+            NormalizedNode::new(&fallback_endpoint, None).priority(1),
+            NormalizedNode::new(&primary_endpoint, None),
+        ])
+        .with_retry_policy(retry_policy().base_delay(Duration::from_secs(5)));
+
+        conn.check_health().await;
+        assert!(conn.node_health().iter().all(|health| health.healthy));
+
+        conn.list_outputs("key", None).await.unwrap();
+        primary.inject_fault(Fault::DropConnection);
+        conn.list_outputs("key", None).await.unwrap();
+
+        let health = conn.node_health();
+        assert_eq!(health[0].endpoint, fallback_endpoint);
+        // one probe and the request retried after the primary dropped the connection
+        assert_eq!(health[0].requests, 2);
+        assert_eq!(health[1].failures, 1);
+        assert!(health[1].backoff.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_backoff_past_deadline_is_not_a_failure() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]).with_retry_policy(
+            retry_policy()
+                .base_delay(Duration::from_secs(5))
+                .timeout(Duration::from_secs(1)),
+        );
+
+        node.inject_fault(Fault::DropConnection);
+        let result = conn.list_outputs("key", None).await;
+        assert!(matches!(result, Err(Error::RequestTimeout)));
+
+        // only the dropped connection counts, not the attempts the backoff prevented
+        let connection = &conn.transport.connection_pool[0];
+        assert_eq!(*connection.retries.lock().unwrap(), 1);
+        assert_eq!(conn.node_health()[0].failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_too_many_requests() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()]).with_retry_policy(retry_policy());
+
+        node.inject_fault(Fault::TooManyRequests(1));
+        let start = Instant::now();
+        assert!(conn.list_outputs("key", None).await.unwrap().is_empty());
+        // waited for `Retry-After` rather than the 10ms backoff of the policy
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        // the node was up, so the answer does not count against it
+        assert_eq!(conn.node_health()[0].failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()])
+            .with_rate_limit(RateLimit::per_second(20.0).burst(1));
+
+        let start = Instant::now();
+        for _ in 0..5 {
+            conn.list_outputs("key", None).await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_exceeded() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let conn = Connection::new(vec![endpoint.as_str()])
+            .with_retry_policy(retry_policy().timeout(Duration::from_millis(200)))
+            .with_rate_limit(RateLimit::per_second(1.0).burst(1));
+
+        conn.list_outputs("key", None).await.unwrap();
+        // the next token comes after the deadline, which is the client's doing, not the node's
+        assert!(matches!(
+            conn.list_outputs("key", None).await,
+            Err(Error::RateLimitExceeded(_))
+        ));
+        let health = &conn.node_health()[0];
+        assert_eq!(health.failures, 0);
+        assert_eq!(health.backoff, None);
+    }
+}
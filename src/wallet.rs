@@ -0,0 +1,347 @@
+//! Balances and transfers of divisible assets held by a set of keys.
+//!
+//! The wallet keeps the unspent outputs of its keys, fetched by `sync` and updated by the
+//! transfers it posts. Only outputs held by a single key of the wallet are tracked, since the
+//! wallet could not spend the others alone. Coin selection takes the largest outputs first, so
+//! a transfer spends as few outputs as possible.
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde_json::Value as JsonValue;
+
+use crate::{
+    batch::PostMode,
+    connection::Connection,
+    error::Error,
+    keys::{PublicKey, SecretKey},
+    transaction::{Output, Transaction, TransactionTemplate, UnspentOutput},
+};
+
+#[derive(Debug, Clone)]
+pub struct Wallet<'a> {
+    connection: Connection<'a>,
+    keys: Vec<SecretKey>,
+    unspent_outputs: Vec<UnspentOutput>,
+}
+
+fn output_of(unspent: &UnspentOutput) -> &Output {
+    &unspent.tx.outputs[unspent.output_index]
+}
+
+fn amount_of(unspent: &UnspentOutput) -> u64 {
+    output_of(unspent).amount.parse().unwrap_or(0)
+}
+
+fn overflow() -> Error {
+    Error::InvalidAmount(String::from("the amounts overflow"))
+}
+
+impl<'a> Wallet<'a> {
+    pub fn new(connection: Connection<'a>) -> Self {
+        Self {
+            connection,
+            keys: Vec::new(),
+            unspent_outputs: Vec::new(),
+        }
+    }
+
+    pub fn with_key(mut self, key: SecretKey) -> Self {
+        self.add_key(key);
+        self
+    }
+
+    /// Track `key`, whose outputs are fetched by the next `sync`.
+    pub fn add_key(&mut self, key: SecretKey) {
+        if !self.keys.contains(&key) {
+            self.keys.push(key);
+        }
+    }
+
+    pub fn public_keys(&self) -> Vec<PublicKey> {
+        self.keys.iter().map(SecretKey::public_key).collect()
+    }
+
+    pub fn unspent_outputs(&self) -> &[UnspentOutput] {
+        &self.unspent_outputs
+    }
+
+    // the key holding `output` alone, if it belongs to the wallet
+    fn owner_of(&self, output: &Output) -> Option<&SecretKey> {
+        match output.public_keys.as_slice() {
+            [owner] => self
+                .keys
+                .iter()
+                .find(|key| key.public_key().to_string() == *owner),
+            _ => None,
+        }
+    }
+
+    fn tracks(&self, unspent: &UnspentOutput) -> bool {
+        unspent
+            .tx
+            .outputs
+            .get(unspent.output_index)
+            .is_some_and(|output| {
+                self.owner_of(output).is_some() && output.amount.parse::<u64>().is_ok()
+            })
+    }
+
+    /// Replace the tracked outputs with the unspent outputs of every key on the ledger.
+    pub async fn sync(&mut self) -> Result<(), Error> {
+        let mut unspent_outputs = Vec::new();
+        let mut seen = HashSet::new();
+
+        for public_key in self.public_keys() {
+            let outputs = self
+                .connection
+                .get_unspent_outputs(&public_key.to_string(), None)
+                .await?;
+            for unspent in outputs {
+                let reference = (unspent.tx.id.clone(), unspent.output_index);
+                if self.tracks(&unspent) && seen.insert(reference) {
+                    unspent_outputs.push(unspent);
+                }
+            }
+        }
+
+        self.unspent_outputs = unspent_outputs;
+        Ok(())
+    }
+
+    /// Amount held of every asset, by asset id.
+    pub fn balances(&self) -> Result<BTreeMap<String, u64>, Error> {
+        let mut balances = BTreeMap::new();
+        for unspent in self.unspent_outputs.iter() {
+            if let Some(asset_id) = unspent.tx.asset_id() {
+                let balance: &mut u64 = balances.entry(asset_id).or_insert(0);
+                *balance = balance
+                    .checked_add(amount_of(unspent))
+                    .ok_or_else(overflow)?;
+            }
+        }
+        Ok(balances)
+    }
+
+    pub fn balance(&self, asset_id: &str) -> Result<u64, Error> {
+        Ok(self.balances()?.get(asset_id).copied().unwrap_or(0))
+    }
+
+    /// Outputs of `asset_id` holding at least `amount`, the largest first.
+    pub fn select_outputs(&self, asset_id: &str, amount: u64) -> Result<Vec<UnspentOutput>, Error> {
+        let mut candidates: Vec<&UnspentOutput> = self
+            .unspent_outputs
+            .iter()
+            .filter(|unspent| unspent.tx.asset_id().as_deref() == Some(asset_id))
+            .collect();
+        candidates.sort_by_key(|unspent| std::cmp::Reverse(amount_of(unspent)));
+
+        let mut selected = Vec::new();
+        let mut total: u64 = 0;
+        for unspent in candidates {
+            if total >= amount && !selected.is_empty() {
+                break;
+            }
+            total = total.checked_add(amount_of(unspent)).ok_or_else(overflow)?;
+            selected.push(unspent.clone());
+        }
+
+        if total < amount || selected.is_empty() {
+            return Err(Error::InsufficientFunds {
+                asset_id: asset_id.to_string(),
+                requested: amount,
+                available: total,
+            });
+        }
+        Ok(selected)
+    }
+
+    /// Signed transfer of `asset_id` to `recipients`, the change going back to `change` or, by
+    /// default, to the owner of the largest output spent.
+    pub fn make_transfer(
+        &self,
+        asset_id: &str,
+        recipients: &[(PublicKey, u64)],
+        change: Option<PublicKey>,
+        metadata: Option<JsonValue>,
+    ) -> Result<TransactionTemplate, Error> {
+        if recipients.is_empty() || recipients.iter().any(|(_, amount)| *amount == 0) {
+            return Err(Error::InvalidAmount(String::from(
+                "every recipient gets a positive amount",
+            )));
+        }
+        let amount = recipients
+            .iter()
+            .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+            .ok_or_else(overflow)?;
+
+        let selected = self.select_outputs(asset_id, amount)?;
+        let keys: Vec<&SecretKey> = selected
+            .iter()
+            .map(|unspent| self.owner_of(output_of(unspent)).unwrap())
+            .collect();
+
+        let mut outputs: Vec<Output> = recipients
+            .iter()
+            .map(|(public_key, amount)| Transaction::make_ed25519_output(public_key, *amount))
+            .collect();
+        let selected_amount = selected
+            .iter()
+            .try_fold(0u64, |total, unspent| total.checked_add(amount_of(unspent)))
+            .ok_or_else(overflow)?;
+        if selected_amount > amount {
+            let change = change.unwrap_or_else(|| keys[0].public_key());
+            outputs.push(Transaction::make_ed25519_output(
+                &change,
+                selected_amount - amount,
+            ));
+        }
+
+        let transaction = Transaction::make_transfer_transaction(selected, outputs, metadata);
+        Transaction::sign_transaction(&transaction, keys)
+    }
+
+    /// Make, sign and post a transfer, see `make_transfer`. The outputs it spends stop being
+    /// tracked and the outputs it gives to the wallet are tracked right away.
+    pub async fn transfer(
+        &mut self,
+        asset_id: &str,
+        recipients: &[(PublicKey, u64)],
+        change: Option<PublicKey>,
+        metadata: Option<JsonValue>,
+        mode: PostMode,
+    ) -> Result<TransactionTemplate, Error> {
+        let transaction = self.make_transfer(asset_id, recipients, change, metadata)?;
+        let transaction = self
+            .connection
+            .post_transaction(mode.path(), transaction)
+            .await?;
+
+        self.record(&transaction);
+        Ok(transaction)
+    }
+
+    // update the tracked outputs with a transaction posted by the wallet
+    fn record(&mut self, transaction: &TransactionTemplate) {
+        let spent: Vec<(Option<String>, u64)> = transaction
+            .inputs
+            .iter()
+            .filter_map(|input| {
+                let fulfills = input.fulfills.as_ref()?;
+                Some((
+                    fulfills.get("transaction_id")?.as_str().map(String::from),
+                    fulfills.get("output_index")?.as_u64()?,
+                ))
+            })
+            .collect();
+        self.unspent_outputs.retain(|unspent| {
+            !spent.contains(&(unspent.tx.id.clone(), unspent.output_index as u64))
+        });
+
+        for output_index in 0..transaction.outputs.len() {
+            let unspent = UnspentOutput {
+                output_index,
+                tx: transaction.clone(),
+            };
+            if self.tracks(&unspent) {
+                self.unspent_outputs.push(unspent);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::MockNode;
+
+    fn issue(node: &MockNode, owner: &SecretKey, amounts: &[u64]) -> String {
+        let outputs = amounts
+            .iter()
+            .map(|amount| Transaction::make_ed25519_output(&owner.public_key(), *amount))
+            .collect();
+        let transaction = Transaction::sign_transaction(
+            &Transaction::make_create_transaction(
+                Some(serde_json::json!({ "name": "token" })),
+                None,
+                outputs,
+                vec![owner.public_key().to_string()],
+            ),
+            vec![owner],
+        )
+        .unwrap();
+        node.commit(transaction.clone()).unwrap();
+        transaction.id.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_balances_and_transfer_with_change() {
+        let node = MockNode::start().await.unwrap();
+        let endpoint = node.endpoint();
+        let alice = SecretKey::generate();
+        let bob = SecretKey::generate();
+        let carol = SecretKey::generate().public_key();
+
+        let token = issue(&node, &alice, &[5, 3]);
+        issue(&node, &bob, &[2]);
+        let other = issue(&node, &bob, &[7]);
+
+        let mut wallet = Wallet::new(Connection::new(vec![endpoint.as_str()]))
+            .with_key(alice.clone())
+            .with_key(bob.clone());
+        wallet.sync().await.unwrap();
+        assert_eq!(wallet.balance(&token).unwrap(), 8);
+        assert_eq!(wallet.balance(&other).unwrap(), 7);
+        assert_eq!(wallet.balances().unwrap().len(), 3);
+
+        // the largest output alone covers 4
+        let selected = wallet.select_outputs(&token, 4).unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(wallet.select_outputs(&token, 6).unwrap().len(), 2);
+        assert!(matches!(
+            wallet.select_outputs(&token, 9),
+            Err(Error::InsufficientFunds { available: 8, .. })
+        ));
+
+        let transaction = wallet
+            .transfer(&token, &[(carol, 6)], None, None, PostMode::Commit)
+            .await
+            .unwrap();
+        assert_eq!(transaction.outputs.len(), 2);
+        assert_eq!(transaction.outputs[1].amount, "2");
+        assert_eq!(wallet.balance(&token).unwrap(), 2);
+
+        // the node agrees with the local bookkeeping
+        wallet.sync().await.unwrap();
+        assert_eq!(wallet.balance(&token).unwrap(), 2);
+        assert_eq!(wallet.balance(&other).unwrap(), 7);
+
+        // the change of a transfer can go to another key of the wallet
+        let transaction = wallet
+            .transfer(
+                &other,
+                &[(carol, 3)],
+                Some(alice.public_key()),
+                None,
+                PostMode::Commit,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            transaction.outputs[1].public_keys,
+            vec![alice.public_key().to_string()]
+        );
+        assert_eq!(wallet.balance(&other).unwrap(), 4);
+
+        // outputs whose total overflows are an error, not a wrapped balance
+        let dave = SecretKey::generate();
+        let huge = issue(&node, &dave, &[u64::MAX - 1, u64::MAX - 1]);
+        let mut wallet = Wallet::new(Connection::new(vec![endpoint.as_str()])).with_key(dave);
+        wallet.sync().await.unwrap();
+        assert!(matches!(wallet.balances(), Err(Error::InvalidAmount(_))));
+        assert_eq!(wallet.select_outputs(&huge, 5).unwrap().len(), 1);
+        assert!(matches!(
+            wallet.select_outputs(&huge, u64::MAX),
+            Err(Error::InvalidAmount(_))
+        ));
+    }
+}